@@ -1,5 +1,9 @@
 use std::{env::args, process::exit};
 
+use strawberryvm::prelude::MEMORY_KILO_BYTES;
+
+use crate::helpers::parse_memory_size;
+
 pub fn usage() {
     println!(
         "
@@ -11,6 +15,9 @@ Usage: jasm <program.jam> [options]
 -o, --output  | Output file (where to write the file to)
 -r, --run     | Automatically run after compiling (won't write a file when this flag is used unless output argument is specified.)
 -R, --reverse | Disassemble a binary back into Jam.
+-m, --memory  | Memory size of the machine used by --run, e.g. 4096, $1000 or 64K (defaults to 1K.)
+--max-cycles  | Stop --run with an error after this many cycles.
+-s, --symbols | Write debug info naming the program's labels to a file, for svm --symbols.
 
 Notes:
     If simply just the file name is specified or just an input flag is specified, the program will take the file stem and write out a binary file with the same file stem.
@@ -19,20 +26,42 @@ Notes:
 Example usages:
     jasm main.jam -o out.bin
     jasm main.jam -r
+    jasm main.jam -r --memory 64K
+    jasm main.jam -r --max-cycles 100000
     jasm main.jam
+    jasm main.jam --symbols main.dbg
 
     jasm main.bin -R > dis.jam
 "
     );
 }
 
-#[derive(Default)]
 pub struct Arguments {
     pub input: Option<String>,
     pub output: Option<String>,
+    pub symbols: Option<String>,
 
     pub reverse: bool,
     pub run: bool,
+
+    pub memory_size: usize,
+    pub max_cycles: Option<u64>,
+}
+
+impl Default for Arguments {
+    fn default() -> Self {
+        Self {
+            input: None,
+            output: None,
+            symbols: None,
+
+            reverse: false,
+            run: false,
+
+            memory_size: MEMORY_KILO_BYTES * 1024,
+            max_cycles: None,
+        }
+    }
 }
 
 impl Arguments {
@@ -72,6 +101,10 @@ impl Arguments {
                     self.output = v.clone();
                 }
 
+                ("-s", Some(v)) | ("--symbols", Some(v)) => {
+                    self.symbols = Some(v.clone());
+                }
+
                 ("-r", None) | ("--run", None) => {
                     self.run = true;
                 }
@@ -80,6 +113,22 @@ impl Arguments {
                     self.reverse = true;
                 }
 
+                ("-m", Some(v)) | ("--memory", Some(v)) => match parse_memory_size(v) {
+                    Ok(size) => self.memory_size = size,
+                    Err(e) => {
+                        eprintln!("Invalid memory size `{v}`: {e}");
+                        exit(1);
+                    }
+                },
+
+                ("--max-cycles", Some(v)) => match v.parse() {
+                    Ok(cycles) => self.max_cycles = Some(cycles),
+                    Err(e) => {
+                        eprintln!("Invalid cycle count `{v}`: {e}");
+                        exit(1);
+                    }
+                },
+
                 _ => {
                     usage();
                     exit(1);
@@ -1,10 +1,13 @@
 use std::str::FromStr;
 
+use crate::debug_info::DebugInfo;
+use crate::directives::Directive;
 use crate::helpers::split_u16;
 use crate::helpers::DynErr;
 
 use crate::parsing::validate_line;
 use crate::parsing::JamParseError;
+use crate::passes::labels::{collect_labels_pass, resolve_labels_pass, split_label, Labels};
 use crate::passes::*;
 
 use strawberryvm::prelude::*;
@@ -12,10 +15,67 @@ use strawberryvm::prelude::*;
 pub struct Assembler();
 
 impl Assembler {
+    /// Assembles a whole program. Macros and constants are expanded
+    /// first, then labels are collected so that `^Name` operands can
+    /// refer to labels defined later on. Directives such as `.word`
+    /// emit data between the instructions.
     pub fn parse_vec(&self, input: &[String]) -> Result<Vec<u8>, DynErr> {
+        self.assemble(input).map(|(bytes, _)| bytes)
+    }
+
+    /// Assembles a whole program like `parse_vec`, also returning the
+    /// debug info describing it.
+    pub fn assemble(&self, input: &[String]) -> Result<(Vec<u8>, DebugInfo), DynErr> {
+        let expansion = pre::expand_macros_pass(input)?;
+
+        self.assemble_expanded(&expansion)
+            .map_err(|e| match e.downcast::<JamParseError>() {
+                Ok(error) => match expansion.origins.get(error.line()) {
+                    Some(origin) => error.at(origin).into(),
+                    None => error,
+                },
+                Err(e) => e,
+            })
+    }
+
+    /// Assembles a program after its macros have been expanded. Errors
+    /// are on lines of the expanded program.
+    fn assemble_expanded(
+        &self,
+        expansion: &pre::Expansion,
+    ) -> Result<(Vec<u8>, DebugInfo), DynErr> {
+        let input = &expansion.lines;
+        let labels = collect_labels_pass(input)?;
+
         let mut out = Vec::new();
+        let mut lines = Vec::new();
         for (index, line) in input.iter().enumerate() {
-            let dbyte = match self.parse_line(String::from(line), index) {
+            let Some(text) = pre::remove_comments_pass(line) else {
+                continue;
+            };
+
+            let (_, text) = split_label(&text);
+
+            if let Some(directive) = Directive::parse(text, index)? {
+                if let Directive::Org(org) = directive {
+                    if usize::from(org) < out.len() {
+                        let message = format!(".org ${org:04X} is behind ${:04X}", out.len());
+                        return Err(JamParseError::InvalidDirective(message, index).into());
+                    }
+
+                    out.resize(usize::from(org), 0);
+                }
+
+                out.extend(directive.emit(&labels, index)?);
+                continue;
+            }
+
+            // Instructions are word aligned, data before them may not be
+            let text = forms::forms_pass(text, &labels, index)?;
+            let address = u16::try_from((out.len() + 1) & !1)?;
+            let text = resolve_labels_pass(&text, address, &labels, index)?;
+
+            let words = match self.parse_line(text, index) {
                 Ok(v) => v,
                 Err(e) => {
                     if let Some(JamParseError::Empty(_)) = e.downcast_ref::<JamParseError>() {
@@ -26,32 +86,463 @@ impl Assembler {
                 }
             };
 
-            let (lower, upper) = split_u16(dbyte);
+            out.resize(usize::from(address), 0);
+            lines.push((address, expansion.origins[index].source_line() + 1));
+
+            for dbyte in words {
+                let (lower, upper) = split_u16(dbyte);
+
+                out.push(lower);
+                out.push(upper);
+            }
+        }
+
+        let mut labels: Vec<_> = labels.into_iter().map(|(name, a)| (a, name)).collect();
+        labels.sort();
+
+        let info = DebugInfo {
+            source: None,
+            labels,
+            lines,
+        };
+
+        Ok((out, info))
+    }
+
+    /// Decodes a binary back into its instructions, as `jasm -R` does.
+    /// Data is decoded as if it were instructions.
+    pub fn disassemble(&self, program: &[u8]) -> Result<Vec<Instruction>, DynErr> {
+        let word = |index: usize| -> Option<u16> {
+            Some(u16::from_le_bytes([
+                *program.get(index)?,
+                *program.get(index + 1)?,
+            ]))
+        };
+
+        let mut instructions = Vec::new();
+        let mut index = 0;
+
+        while index < program.len() {
+            let Some(value) = word(index) else {
+                return Err("Incomplete data for instruction decoding.".into());
+            };
+
+            let size = usize::from(Instruction::encoded_size(value));
+            let extension = if size > 2 {
+                word(index + 2).ok_or("Incomplete data for instruction decoding.")?
+            } else {
+                0
+            };
 
-            out.push(lower);
-            out.push(upper);
+            instructions.push(Instruction::decode(value, extension)?);
+            index += size;
         }
 
-        Ok(out)
+        Ok(instructions)
     }
 
-    pub fn parse_line(&self, text: String, line_number: usize) -> Result<u16, DynErr> {
+    /// Assembles a single line into its encoded words. Wide instructions
+    /// such as `LoadImm` take up two words.
+    pub fn parse_line(&self, text: String, line_number: usize) -> Result<Vec<u16>, DynErr> {
         let text_slice = text.as_str();
         let cleaned = pre::remove_comments_pass(text_slice);
 
         if let Some(text) = cleaned {
+            let text = forms::forms_pass(&text, &Labels::new(), line_number)?;
             validate_line(&text, line_number)?;
 
-            let parts: &str = &text_slice
+            let parts: &str = &text
                 .split(' ')
                 .filter(|x| !x.is_empty())
                 .collect::<Vec<_>>()
                 .join(" ");
             let instruction = Instruction::from_str(parts)?;
 
-            return Ok(instruction.encode_u16());
+            return Ok(instruction.encode());
         }
 
         Err(JamParseError::Empty(line_number).into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(source: &str) -> Result<Vec<u8>, DynErr> {
+        let lines: Vec<String> = source.lines().map(String::from).collect();
+        Assembler().parse_vec(&lines)
+    }
+
+    fn parse_error(source: &str) -> JamParseError {
+        *assemble(source)
+            .unwrap_err()
+            .downcast::<JamParseError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn labels_resolve_to_relative_offsets() {
+        let bytes = assemble(
+            "Start:\n\
+             Nop\n\
+             Je ^End   ; forwards\n\
+             Jmp ^Start\n\
+             End: Signal $F0",
+        )
+        .unwrap();
+
+        assert_eq!(bytes, vec![0x00, 0x00, 0x32, 0x01, 0x31, 0xFD, 0x50, 0xF0]);
+    }
+
+    #[test]
+    fn wide_immediates() {
+        let bytes = assemble(
+            "LoadImm A $FFFF\n\
+             LoadImm B %1000000000000001\n\
+             LoadImm C 65535\n\
+             LoadImm D ^Data\n\
+             Push $1234\n\
+             Push 300\n\
+             Push 255\n\
+             Data: Nop",
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x14, 0x00, 0xFF, 0xFF, // LoadImm A $FFFF
+                0x14, 0x01, 0x01, 0x80, // LoadImm B %1000000000000001
+                0x14, 0x02, 0xFF, 0xFF, // LoadImm C 65535
+                0x14, 0x03, 0x1A, 0x00, // LoadImm D ^Data
+                0x15, 0x00, 0x34, 0x12, // PushWide $1234
+                0x15, 0x00, 0x2C, 0x01, // PushWide 300
+                0x10, 0xFF, // Push 255
+                0x00, 0x00, // Nop
+            ]
+        );
+
+        assert!(assemble("LoadImm A 65536").is_err());
+        assert!(assemble("Push 65536").is_err());
+
+        let bytes = assemble("Push ^Near\nPush ^Far\nNear: Nop\n.org $100\nFar: Nop").unwrap();
+
+        assert_eq!(
+            bytes[..8],
+            [
+                0x10, 0x06, // Push ^Near
+                0x15, 0x00, 0x00, 0x01, // PushWide ^Far
+                0x00, 0x00, // Nop
+            ]
+        );
+        assert_eq!(bytes.len(), 0x102);
+    }
+
+    #[test]
+    fn bracket_operands() {
+        let bytes = assemble(
+            "Load A [BP - 2]\n\
+             StoreB D [B+C]\n\
+             Load A [B]\n\
+             LoadBS C [B+^Data]\n\
+             Data: Nop",
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x45, 0x60, 0xFE, 0xFF, // LoadOff A [BP-2]
+                0x4E, 0x13, 0x02, 0x00, // StoreBIdx D [B+C]
+                0x40, 0x10, // Load A B
+                0x4B, 0x12, 0x0E, 0x00, // LoadBSOff C [B+14]
+                0x00, 0x00, // Nop
+            ]
+        );
+
+        for source in [
+            "Load A [B+C",
+            "Load A [",
+            "Load A [BP",
+            "Store [] B",
+            "Push [A+1]",
+            "Add A [B]",
+            "Cmp [A] B",
+            "PushReg [C]",
+        ] {
+            assert!(matches!(
+                parse_error(source),
+                JamParseError::InvalidOpCode(..)
+            ));
+        }
+    }
+
+    #[test]
+    fn immediate_operands() {
+        let bytes = assemble("Add A 1\nShl C $3\nCmp B %1010\nAdd A B\nAddI D 15").unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x70, 0x10, // AddI A 1
+                0x72, 0x32, // ShlI C 3
+                0x3D, 0xA1, // CmpI B 10
+                0x20, 0x10, // Add A B
+                0x70, 0xF3, // AddI D 15
+            ]
+        );
+
+        assert!(assemble("Add A 16").is_err());
+        assert!(assemble("Mov A 1").is_err());
+    }
+
+    #[test]
+    fn data_directives() {
+        let bytes = assemble(
+            "LoadImm A ^Text\n\
+             Text: .string \"a;\\\"\\n\" ; comment\n\
+             Bytes: .byte 1, $FF, -1, ^Bytes\n\
+             .word $1234 ^Text\n\
+             .fill 3 %101\n\
+             .org 22\n\
+             Nop",
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x14, 0x00, 0x04, 0x00, // LoadImm A ^Text
+                b'a', b';', b'"', b'\n', 0, // .string
+                1, 0xFF, 0xFF, 9, // .byte
+                0x34, 0x12, 0x04, 0x00, // .word
+                5, 5, 5, // .fill
+                0, 0, // .org 22
+                0x00, 0x00, // Nop
+            ]
+        );
+
+        let (_, info) = Assembler()
+            .assemble(&[".word 1".to_string(), "Nop".to_string()])
+            .unwrap();
+        assert_eq!(info.lines, vec![(2, 2)]);
+
+        for source in [
+            ".org 4\n.org 2",
+            ".byte 256",
+            ".string abc",
+            ".bytes 1",
+            ".fill",
+        ] {
+            assert!(matches!(
+                parse_error(source),
+                JamParseError::InvalidDirective(..)
+            ));
+        }
+    }
+
+    #[test]
+    fn data_alignment() {
+        let bytes = assemble(
+            "Jmp ^Code\n\
+             .string \"ab\"\n\
+             Code: Jmp ^Done\n\
+             .byte 1\n\
+             Done:\n\
+             Signal $F0",
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x31, 0x02, // Jmp ^Code
+                b'a', b'b', 0, // .string
+                0, // padding
+                0x31, 0x01, // Jmp ^Done
+                1,    // .byte
+                0,    // padding
+                0x50, 0xF0, // Signal $F0
+            ]
+        );
+
+        let mut vm = crate::runner::load(&bytes, 1024).unwrap();
+        assert_eq!(vm.run_for(1000).unwrap(), RunOutcome::Halted);
+
+        let bytes = assemble("Jmp ^Main\nMain: .org $10\nSignal $F0").unwrap();
+        assert_eq!(&bytes[..2], [0x31, 0x07]); // Jmp ^Main
+        assert_eq!(&bytes[0x10..], [0x50, 0xF0]); // Main: Signal $F0
+
+        let mut vm = crate::runner::load(&bytes, 1024).unwrap();
+        assert_eq!(vm.run_for(1000).unwrap(), RunOutcome::Halted);
+
+        assert!(matches!(
+            parse_error("Jmp ^Data\n.byte 1\nData: .byte 2"),
+            JamParseError::MisalignedLabel(..)
+        ));
+    }
+
+    #[test]
+    fn macros_and_constants() {
+        let lines: Vec<String> = ".equ Ten 10\n\
+                                  .equ Local -2\n\
+                                  .macro PushImm value, reg ; comment\n\
+                                  Push value\n\
+                                  Pop reg\n\
+                                  .endm\n\
+                                  .macro Wait reg\n\
+                                  Loop: SubI reg 1\n\
+                                  Jne ^Loop\n\
+                                  .endm\n\
+                                  Start: PushImm Ten A\n\
+                                  Wait A\n\
+                                  Wait B\n\
+                                  Load C [BP+Local]\n\
+                                  .byte Ten"
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let (bytes, info) = Assembler().assemble(&lines).unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0x10, 0x0A, 0x11, 0x00, // PushImm Ten A
+                0x71, 0x10, 0x33, 0xFE, // Wait A
+                0x71, 0x11, 0x33, 0xFE, // Wait B
+                0x45, 0x62, 0xFE, 0xFF, // Load C [BP-2]
+                0x0A, // .byte Ten
+            ]
+        );
+
+        assert_eq!(info.labels[0], (0, "Start".to_string()));
+        assert_eq!(info.labels.len(), 3);
+        assert_eq!(info.line_of(2), Some(11));
+        assert_eq!(info.line_of(8), Some(13));
+    }
+
+    #[test]
+    fn macro_errors() {
+        let error =
+            parse_error(".macro Inner\nJmp ^Nowhere\n.endm\n.macro Outer\nInner\n.endm\nOuter");
+
+        assert_eq!(
+            error.to_string(),
+            "Undefined label `^Nowhere` on line 2, in macro `Inner` called on line 5, \
+             in macro `Outer` called on line 7"
+        );
+        assert_eq!(error.line(), 6);
+
+        assert_eq!(
+            parse_error("Nop\nFoo A").to_string(),
+            "Error at Unknown opcode Foo on line 2"
+        );
+
+        let error = parse_error(".macro M\nNop\nFoo A\n.endm\nNop\nNop\nM");
+        assert_eq!(
+            error.to_string(),
+            "Error at Unknown opcode Foo on line 3, in macro `M` called on line 7"
+        );
+
+        for source in [
+            ".macro M a\nNop\n.endm\nM",
+            ".macro M\nNop",
+            ".macro M\nM\n.endm\nM",
+            ".equ A 1",
+            ".endm",
+        ] {
+            assert!(assemble(source).is_err());
+        }
+    }
+
+    #[test]
+    fn calls_round_trip() {
+        let source = "LoadImm A 1\n\
+                      Call ^Double\n\
+                      Call ^Double\n\
+                      Signal $F0\n\
+                      Double: Add A A\n\
+                      Ret";
+        let bytes = assemble(source).unwrap();
+
+        let mut vm = crate::runner::load(&bytes, 1024).unwrap();
+        assert_eq!(vm.run_for(1000).unwrap(), RunOutcome::Halted);
+        assert_eq!(vm.get_register(Register::A), 4);
+
+        let text: Vec<String> = Assembler()
+            .disassemble(&bytes)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            text,
+            [
+                "LoadImm A 1",
+                "Call 2",
+                "Call 1",
+                "Signal 240",
+                "Add A A",
+                "Ret"
+            ]
+        );
+        assert_eq!(assemble(&text.join("\n")).unwrap(), bytes);
+    }
+
+    #[test]
+    fn debug_info_lists_labels() {
+        let lines: Vec<String> = "Start: Nop\nLoadImm A 1\nEnd: Signal $F0"
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let (_, info) = Assembler().assemble(&lines).unwrap();
+        assert_eq!(
+            info.labels,
+            vec![(0, "Start".to_string()), (6, "End".to_string())]
+        );
+
+        let mut text = Vec::new();
+        info.write_to(&mut text).unwrap();
+        assert_eq!(
+            DebugInfo::parse(&String::from_utf8(text).unwrap()).unwrap(),
+            info
+        );
+
+        assert_eq!(info.lines, vec![(0, 1), (2, 2), (6, 3)]);
+        assert_eq!(info.line_of(2), Some(2));
+        assert_eq!(info.line_of(4), None);
+
+        assert_eq!(info.symbolize(4), "Start+4");
+        assert_eq!(info.symbolize(6), "End");
+    }
+
+    #[test]
+    fn undefined_label() {
+        assert!(matches!(
+            parse_error("Jmp ^Nowhere"),
+            JamParseError::UndefinedLabel(name, 0) if name == "Nowhere"
+        ));
+    }
+
+    #[test]
+    fn duplicate_label() {
+        assert!(matches!(
+            parse_error("Here:\nNop\nHere: Nop"),
+            JamParseError::DuplicateLabel(name, 2) if name == "Here"
+        ));
+    }
+
+    #[test]
+    fn label_out_of_range() {
+        let source = format!("Jmp ^Far\n{}Far: Nop", "Nop\n".repeat(200));
+
+        assert!(matches!(
+            parse_error(&source),
+            JamParseError::LabelOutOfRange(name, 0) if name == "Far"
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::Write;
+
+use crate::helpers::DynErr;
+
+/// What the assembler knows about a program that the binary doesn't,
+/// written with `jasm --symbols` so that tools such as `svm --profile`
+/// can name addresses.
+///
+/// The file is plain text with one entry per line:
+///
+/// ```text
+/// source loop.jam
+/// label $000C Loop
+/// line $000C 4
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// Path of the assembled source file, as it was given to jasm.
+    pub source: Option<String>,
+    /// Labels with the address they were defined at, in address order.
+    pub labels: Vec<(u16, String)>,
+    /// The address of every instruction with the source line it was
+    /// assembled from, counting from 1, in address order.
+    pub lines: Vec<(u16, usize)>,
+}
+
+impl DebugInfo {
+    /// Writes the debug info in the text format.
+    pub fn write_to(&self, mut out: impl Write) -> Result<(), DynErr> {
+        if let Some(source) = &self.source {
+            writeln!(out, "source {source}")?;
+        }
+
+        for (address, name) in &self.labels {
+            writeln!(out, "label ${address:04X} {name}")?;
+        }
+
+        for (address, line) in &self.lines {
+            writeln!(out, "line ${address:04X} {line}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads debug info in the text format. Blank lines, comments
+    /// starting with `;` and unknown entries are skipped.
+    pub fn parse(text: &str) -> Result<Self, DynErr> {
+        let mut info = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            if let Some(source) = line.strip_prefix("source ") {
+                info.source = Some(source.to_string());
+                continue;
+            }
+
+            let line = line.split(';').next().unwrap_or_default();
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            let invalid = || format!("Invalid debug info on line {}", index + 1);
+            let address = |address: &str| {
+                address
+                    .strip_prefix('$')
+                    .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                    .ok_or_else(invalid)
+            };
+
+            match parts.as_slice() {
+                ["label", a, name] => info.labels.push((address(a)?, (*name).to_string())),
+                ["line", a, line] => info.lines.push((address(a)?, line.parse()?)),
+                _ => (),
+            }
+        }
+
+        info.labels.sort();
+        info.lines.sort_unstable();
+        Ok(info)
+    }
+
+    /// The closest label at or before `address`, with the distance to it.
+    pub fn label_before(&self, address: u16) -> Option<(&str, u16)> {
+        let index = self.labels.partition_point(|(a, _)| *a <= address);
+        let (start, name) = self.labels.get(index.checked_sub(1)?)?;
+
+        Some((name, address - start))
+    }
+
+    /// The source line of the instruction at `address`.
+    pub fn line_of(&self, address: u16) -> Option<usize> {
+        let index = self
+            .lines
+            .binary_search_by_key(&address, |(a, _)| *a)
+            .ok()?;
+        Some(self.lines[index].1)
+    }
+
+    /// Names an address as `Label` or `Label+offset`, or as `$XXXX`
+    /// when no label comes before it.
+    pub fn symbolize(&self, address: u16) -> String {
+        match self.label_before(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{name}+{offset}"),
+            None => format!("${address:04X}"),
+        }
+    }
+}
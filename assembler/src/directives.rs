@@ -0,0 +1,171 @@
+use strawberryvm::prelude::Instruction;
+
+use crate::parsing::JamParseError;
+use crate::passes::labels::Labels;
+
+/// A data or layout directive, a line such as `.byte 1, 2, 3`. Data
+/// directives emit bytes in place of an instruction, `.org` moves the
+/// address the following lines are assembled at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    /// `.byte 1, $FF, ^Label`, one byte per value.
+    Byte(Vec<String>),
+    /// `.word $1234, ^Label`, one little endian word per value.
+    Word(Vec<String>),
+    /// `.string "Hi\n"`, the bytes of the string followed by a NUL.
+    String(Vec<u8>),
+    /// `.fill 16` or `.fill 16 $FF`, a byte repeated a number of times.
+    Fill(u16, u8),
+    /// `.org $100`, continues at an address, leaving a gap of zeroes.
+    Org(u16),
+}
+
+fn invalid(message: impl Into<String>, line: usize) -> JamParseError {
+    JamParseError::InvalidDirective(message.into(), line)
+}
+
+/// Parses a number that must lie in `min..=max`, negative numbers are
+/// kept in two's complement.
+fn number(text: &str, min: i64, max: i64, line: usize) -> Result<i64, JamParseError> {
+    let value =
+        Instruction::parse_immediate::<i64>(text).map_err(|e| invalid(format!("{e}"), line))?;
+
+    if value < min || value > max {
+        return Err(invalid(format!("`{text}` is out of range"), line));
+    }
+
+    Ok(value)
+}
+
+/// Unescapes the contents of a `"..."` string, supporting `\n`, `\t`,
+/// `\0`, `\\` and `\"`.
+fn string(text: &str, line: usize) -> Result<Vec<u8>, JamParseError> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or_else(|| invalid(format!("expected a quoted string, got `{text}`"), line))?;
+
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"')) => c,
+                other => {
+                    let escape = other.map(String::from).unwrap_or_default();
+                    return Err(invalid(format!("unknown escape `\\{escape}`"), line));
+                }
+            },
+            '"' => return Err(invalid("unescaped `\"` in string", line)),
+            c => c,
+        };
+
+        let mut buffer = [0; 4];
+        bytes.extend(c.encode_utf8(&mut buffer).as_bytes());
+    }
+
+    bytes.push(0);
+    Ok(bytes)
+}
+
+impl Directive {
+    /// Parses a line (without its label or comment), returning `None`
+    /// when it is not a directive.
+    pub fn parse(text: &str, line: usize) -> Result<Option<Self>, JamParseError> {
+        let text = text.trim();
+
+        let Some(rest) = text.strip_prefix('.') else {
+            return Ok(None);
+        };
+
+        let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let args = args.trim();
+        let values: Vec<String> = args
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect();
+
+        let directive = match name {
+            "byte" | "word" if values.is_empty() => {
+                return Err(invalid(format!(".{name} needs at least one value"), line))
+            }
+            "byte" => Self::Byte(values),
+            "word" => Self::Word(values),
+            "string" => Self::String(string(args, line)?),
+
+            "fill" => {
+                let value = match values.as_slice() {
+                    [_] => 0,
+                    [_, value] => number(value, -128, 255, line)?,
+                    _ => return Err(invalid(".fill expects a count and a value", line)),
+                };
+
+                let size = number(&values[0], 0, 0xFFFF, line)?;
+                Self::Fill(size as u16, value as u8)
+            }
+
+            "org" => match values.as_slice() {
+                [address] => Self::Org(number(address, 0, 0xFFFF, line)? as u16),
+                _ => return Err(invalid(".org expects an address", line)),
+            },
+
+            _ => return Err(invalid(format!("unknown directive `.{name}`"), line)),
+        };
+
+        Ok(Some(directive))
+    }
+
+    /// Number of bytes emitted, `0` for `.org`.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Byte(values) => values.len(),
+            Self::Word(values) => values.len() * 2,
+            Self::String(bytes) => bytes.len(),
+            Self::Fill(count, _) => usize::from(*count),
+            Self::Org(_) => 0,
+        }
+    }
+
+    /// The bytes emitted, with `^Name` values replaced by the address
+    /// of the label.
+    pub fn emit(&self, labels: &Labels, line: usize) -> Result<Vec<u8>, JamParseError> {
+        let value = |text: &str, min: i64, max: i64| match text.strip_prefix('^') {
+            Some(name) => {
+                let target = *labels
+                    .get(name)
+                    .ok_or_else(|| JamParseError::UndefinedLabel(name.to_string(), line))?;
+
+                if i64::from(target) > max {
+                    return Err(JamParseError::LabelOutOfRange(name.to_string(), line));
+                }
+
+                Ok(i64::from(target))
+            }
+            None => number(text, min, max, line),
+        };
+
+        let bytes = match self {
+            Self::Byte(values) => values
+                .iter()
+                .map(|v| value(v, -128, 255).map(|v| v as u8))
+                .collect::<Result<_, _>>()?,
+
+            Self::Word(values) => values
+                .iter()
+                .map(|v| value(v, -32768, 0xFFFF).map(|v| (v as u16).to_le_bytes()))
+                .collect::<Result<Vec<_>, _>>()?
+                .concat(),
+
+            Self::String(bytes) => bytes.clone(),
+            Self::Fill(count, value) => vec![*value; usize::from(*count)],
+            Self::Org(_) => Vec::new(),
+        };
+
+        Ok(bytes)
+    }
+}
@@ -1,3 +1,5 @@
+use strawberryvm::prelude::MAX_MEMORY_BYTES;
+
 pub type DynErr = Box<dyn std::error::Error>;
 
 pub fn assert_length(parts: &[&str], n: usize) -> Result<(), Box<dyn std::error::Error>> {
@@ -14,3 +16,41 @@ pub fn split_u16(dbyte: u16) -> (u8, u8) {
 
     (lower, upper)
 }
+
+/// Parses a memory size given on the command line. Accepts plain bytes
+/// (`4096`), hexadecimal (`$1000`) or kilobytes (`64K`, `64KiB`), from
+/// 1 byte up to the 64 KiB a machine can address.
+pub fn parse_memory_size(s: &str) -> Result<usize, DynErr> {
+    let kilo = s
+        .strip_suffix("KiB")
+        .or_else(|| s.strip_suffix('K'))
+        .or_else(|| s.strip_suffix('k'));
+
+    let size = match (kilo, s.strip_prefix('$')) {
+        (Some(n), _) => n.parse::<usize>()?.checked_mul(1024),
+        (None, Some(hex)) => Some(usize::from_str_radix(hex, 16)?),
+        (None, None) => Some(s.parse::<usize>()?),
+    };
+
+    match size {
+        Some(size @ 1..=MAX_MEMORY_BYTES) => Ok(size),
+        _ => Err(format!("Memory size must be between 1 and {MAX_MEMORY_BYTES} bytes").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_sizes() {
+        assert_eq!(parse_memory_size("4096").unwrap(), 4096);
+        assert_eq!(parse_memory_size("$1000").unwrap(), 4096);
+        assert_eq!(parse_memory_size("64K").unwrap(), MAX_MEMORY_BYTES);
+        assert_eq!(parse_memory_size("1KiB").unwrap(), 1024);
+
+        for size in ["0", "65K", "$10001", "99999999999999999K", "-1", "big"] {
+            assert!(parse_memory_size(size).is_err(), "{size}");
+        }
+    }
+}
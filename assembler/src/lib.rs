@@ -1,5 +1,7 @@
 pub mod arguments;
 pub mod assembler;
+pub mod debug_info;
+pub mod directives;
 pub mod helpers;
 pub mod parsing;
 pub mod passes;
@@ -1,17 +1,14 @@
 use jasm::arguments::usage;
 use jasm::arguments::Arguments;
 use jasm::assembler::Assembler;
+use jasm::debug_info::DebugInfo;
 use jasm::helpers::DynErr;
-use jasm::parsing::validate_jam;
 use jasm::parsing::JamParseError;
-use jasm::runner::run;
-
-use jasm::passes::pre::remove_comments_pass;
-use strawberryvm::prelude::Instruction;
+use jasm::runner::{report, run};
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
 use std::path::Path;
 use std::process::exit;
 
@@ -23,6 +20,9 @@ use std::process::exit;
 /// -o, --output  | Output file (where to write the file to)
 /// -r, --run     | Automatically run after compiling (won't write a file when this flag is used unless output argument is specified.)
 /// -R, --reverse | Disassemble a binary back into Jam.
+/// -m, --memory  | Memory size of the machine used by --run, e.g. 4096, $1000 or 64K (defaults to 1K.)
+/// --max-cycles  | Stop --run with an error after this many cycles.
+/// -s, --symbols | Write debug info naming the program's labels to a file, for svm --symbols.
 ///
 /// Notes:
 ///     If simply just the file name is specified or just an input flag is specified, the program will take the file stem and write out a binary file with the same file stem.
@@ -49,17 +49,8 @@ fn main() -> Result<(), DynErr> {
             .read_to_end(&mut program)
             .map_err(|x| format!("read: {}", x))?;
 
-        let mut index = 0;
-
-        while index < program.len() {
-            if index + 2 <= program.len() {
-                let value = u16::from_le_bytes([program[index], program[index + 1]]);
-                let instruction = Instruction::try_from(value)?;
-                println!("{}", instruction);
-                index += 2;
-            } else {
-                return Err("Incomplete data for instruction decoding.".into());
-            }
+        for instruction in assembler.disassemble(&program)? {
+            println!("{}", instruction);
         }
 
         exit(0)
@@ -72,9 +63,9 @@ fn main() -> Result<(), DynErr> {
                 .expect("Failed to read file");
 
             let lines: Vec<String> = contents.lines().map(String::from).collect();
-            let bytes = assembler.parse_vec(&lines);
+            let assembled = assembler.assemble(&lines);
 
-            let bytes = match bytes {
+            let assembled = match assembled {
                 Ok(v) => Ok(v),
                 Err(e) => match e.downcast::<JamParseError>() {
                     Ok(v) => Err(v),
@@ -85,7 +76,7 @@ fn main() -> Result<(), DynErr> {
                 },
             };
 
-            let bytes = match bytes {
+            let (bytes, debug_info) = match assembled {
                 Ok(v) => v,
                 Err(jam_error) => match *jam_error {
                     JamParseError::InvalidOpCode(violation, line) => {
@@ -110,32 +101,22 @@ fn main() -> Result<(), DynErr> {
                 },
             };
 
-            let lines: Vec<String> = lines
-                .iter()
-                .filter_map(|line| remove_comments_pass(line))
-                .collect();
-
-            if let Err(e) = validate_jam(&lines) {
-                eprintln!("Encountered error when validating!");
-
-                match e {
-                    JamParseError::InvalidOpCode(opcode, line) => {
-                        eprintln!("Invalid opcode `{}` on line {}", opcode, line);
+            if let Some(path) = &args.symbols {
+                let debug_info = DebugInfo {
+                    source: Some(input.clone()),
+                    ..debug_info
+                };
 
-                        if let Some(line_content) = lines.get(line - 1) {
-                            let width = line_content.len();
-                            eprintln!("\n{}\n{:~<width$}", line_content, "", width = width);
-                        }
-                    }
-
-                    JamParseError::Empty(_) => (),
-                }
-
-                std::process::exit(1);
+                let mut file = BufWriter::new(File::create(path)?);
+                debug_info.write_to(&mut file)?;
+                file.flush()?;
             }
 
             if args.run {
-                run(&bytes)?;
+                if let Err(e) = run(&bytes, args.memory_size, args.max_cycles) {
+                    report(e.as_ref());
+                    exit(1);
+                }
             }
 
             if !args.run || args.output.is_some() {
@@ -3,10 +3,71 @@ use std::str::FromStr;
 
 use strawberryvm::prelude::{Instruction, InstructionParseError};
 
+use crate::passes::pre::Origin;
+
 #[derive(Debug)]
 pub enum JamParseError {
     InvalidOpCode(String, usize),
     Empty(usize),
+
+    InvalidLabel(String, usize),
+    DuplicateLabel(String, usize),
+    UndefinedLabel(String, usize),
+    LabelOutOfRange(String, usize),
+    /// A jump or call to a label at an odd address, which can not be
+    /// an instruction.
+    MisalignedLabel(String, usize),
+
+    InvalidDirective(String, usize),
+
+    /// An error in a line expanded from a macro, with the name of the
+    /// macro and the line it was called on.
+    InMacro(Box<JamParseError>, String, usize),
+}
+
+impl JamParseError {
+    /// The line the error is on, for errors in a macro the line the
+    /// macro was called on.
+    pub fn line(&self) -> usize {
+        match self {
+            JamParseError::InvalidOpCode(_, line)
+            | JamParseError::Empty(line)
+            | JamParseError::InvalidLabel(_, line)
+            | JamParseError::DuplicateLabel(_, line)
+            | JamParseError::UndefinedLabel(_, line)
+            | JamParseError::LabelOutOfRange(_, line)
+            | JamParseError::MisalignedLabel(_, line)
+            | JamParseError::InvalidDirective(_, line)
+            | JamParseError::InMacro(_, _, line) => *line,
+        }
+    }
+
+    fn with_line(self, line: usize) -> Self {
+        match self {
+            JamParseError::InvalidOpCode(message, _) => JamParseError::InvalidOpCode(message, line),
+            JamParseError::Empty(_) => JamParseError::Empty(line),
+            JamParseError::InvalidLabel(label, _) => JamParseError::InvalidLabel(label, line),
+            JamParseError::DuplicateLabel(label, _) => JamParseError::DuplicateLabel(label, line),
+            JamParseError::UndefinedLabel(label, _) => JamParseError::UndefinedLabel(label, line),
+            JamParseError::LabelOutOfRange(label, _) => JamParseError::LabelOutOfRange(label, line),
+            JamParseError::MisalignedLabel(label, _) => JamParseError::MisalignedLabel(label, line),
+            JamParseError::InvalidDirective(message, _) => {
+                JamParseError::InvalidDirective(message, line)
+            }
+            JamParseError::InMacro(error, name, _) => JamParseError::InMacro(error, name, line),
+        }
+    }
+
+    /// Moves an error on a line of the expanded program to the source
+    /// line it came from, naming every macro it was expanded from.
+    pub fn at(self, origin: &Origin) -> Self {
+        origin
+            .calls
+            .iter()
+            .fold(self.with_line(origin.line), |error, (name, line)| {
+                JamParseError::InMacro(Box::new(error), name.clone(), *line)
+            })
+    }
 }
 
 impl Error for JamParseError {}
@@ -15,11 +76,43 @@ impl std::fmt::Display for JamParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             JamParseError::InvalidOpCode(invalid, line) => {
-                write!(f, "Error at {invalid} on line {line}")
+                write!(f, "Error at {invalid} on line {}", line + 1)
             }
 
             JamParseError::Empty(line) => {
-                write!(f, "Error, empty line {line}")
+                write!(f, "Error, empty line {}", line + 1)
+            }
+
+            JamParseError::InvalidLabel(label, line) => {
+                write!(f, "Invalid label name `{label}` on line {}", line + 1)
+            }
+
+            JamParseError::DuplicateLabel(label, line) => {
+                write!(f, "Label `{label}` redefined on line {}", line + 1)
+            }
+
+            JamParseError::UndefinedLabel(label, line) => {
+                write!(f, "Undefined label `^{label}` on line {}", line + 1)
+            }
+
+            JamParseError::LabelOutOfRange(label, line) => {
+                write!(f, "Label `^{label}` is out of range on line {}", line + 1)
+            }
+
+            JamParseError::MisalignedLabel(label, line) => {
+                write!(
+                    f,
+                    "Label `^{label}` is at an odd address, not an instruction, on line {}",
+                    line + 1
+                )
+            }
+
+            JamParseError::InvalidDirective(message, line) => {
+                write!(f, "Invalid directive, {message} on line {}", line + 1)
+            }
+
+            JamParseError::InMacro(error, name, line) => {
+                write!(f, "{error}, in macro `{name}` called on line {}", line + 1)
             }
         }
     }
@@ -47,11 +140,3 @@ pub fn validate_line(line: &str, index: usize) -> Result<(), JamParseError> {
         Ok(_) => Ok(()),
     }
 }
-
-pub fn validate_jam(lines: &[String]) -> Result<(), JamParseError> {
-    for (line_number, line) in lines.iter().enumerate() {
-        validate_line(line, line_number)?;
-    }
-
-    Ok(())
-}
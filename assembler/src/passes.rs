@@ -1,6 +1,33 @@
 pub mod pre {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use strawberryvm::prelude::{Instruction, Register};
+
+    use crate::parsing::JamParseError;
+    use crate::passes::labels::{is_valid_label, split_label};
+
+    /// How deep macros may call other macros, a macro calling itself
+    /// would otherwise never stop expanding.
+    const MAX_MACRO_DEPTH: usize = 16;
+
+    /// Cuts a line off at its `;` comment, a `;` inside a `"..."`
+    /// string is not a comment. Returns `None` for an empty line.
     pub fn remove_comments_pass(text: &str) -> Option<String> {
-        let first = text.split(';').next().unwrap_or("");
+        let mut quoted = false;
+        let mut escaped = false;
+
+        let end = text.find(|c| {
+            match c {
+                '\\' if quoted => escaped = !escaped,
+                '"' if !escaped => quoted = !quoted,
+                _ => escaped = false,
+            }
+
+            c == ';' && !quoted
+        });
+
+        let first = &text[..end.unwrap_or(text.len())];
 
         if first.is_empty() {
             return None;
@@ -8,6 +35,712 @@ pub mod pre {
 
         Some(String::from(first))
     }
+
+    /// Where a line of the expanded program came from.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct Origin {
+        /// The source line, inside the macro body for expanded lines.
+        pub line: usize,
+        /// The macros being expanded with the line each was called on,
+        /// innermost first.
+        pub calls: Vec<(String, usize)>,
+    }
+
+    impl Origin {
+        /// The line in the program itself, the outermost call site for
+        /// lines expanded from a macro.
+        pub fn source_line(&self) -> usize {
+            self.calls.last().map_or(self.line, |(_, line)| *line)
+        }
+    }
+
+    /// The program with constants and macros expanded, and where each
+    /// of its lines came from.
+    #[derive(Debug, Clone, Default)]
+    pub struct Expansion {
+        pub lines: Vec<String>,
+        pub origins: Vec<Origin>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct Macro {
+        params: Vec<String>,
+        /// The body with the source line of each of its lines.
+        body: Vec<(usize, String)>,
+    }
+
+    #[derive(Default)]
+    struct Expander {
+        constants: HashMap<String, String>,
+        macros: HashMap<String, Macro>,
+        /// Counts expansions, to give each its own local labels.
+        expansions: usize,
+        out: Expansion,
+    }
+
+    /// Calls `replace` with every identifier outside of strings, and
+    /// whether it follows a `^`, putting back what it returns. Numbers
+    /// such as `$FF` are not identifiers.
+    fn replace_identifiers(text: &str, replace: impl Fn(&str, bool) -> Option<String>) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        let mut quoted = false;
+        let mut previous = ' ';
+
+        while let Some(c) = rest.chars().next() {
+            let word = c.is_ascii_alphanumeric() || c == '_';
+
+            if quoted || !word {
+                match c {
+                    '\\' if quoted => {
+                        let escape = rest.chars().take(2).map(char::len_utf8).sum();
+                        out.push_str(&rest[..escape]);
+                        rest = &rest[escape..];
+                        continue;
+                    }
+                    '"' => quoted = !quoted,
+                    _ => (),
+                }
+
+                out.push(c);
+                previous = c;
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let (identifier, after) = rest.split_at(end);
+
+            let number = c.is_ascii_digit() || previous == '$' || previous == '%';
+            match replace(identifier, previous == '^').filter(|_| !number) {
+                Some(replacement) => out.push_str(&replacement),
+                None => out.push_str(identifier),
+            }
+
+            previous = 'a';
+            rest = after;
+        }
+
+        out
+    }
+
+    /// Splits the operands of a line on whitespace and commas, keeping
+    /// bracketed memory operands together.
+    fn split_operands(text: &str) -> Vec<String> {
+        let mut operands = vec![String::new()];
+        let mut depth = 0;
+
+        for c in text.chars() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => (),
+            }
+
+            if depth == 0 && (c == ',' || c.is_whitespace()) {
+                operands.push(String::new());
+            } else if let Some(operand) = operands.last_mut() {
+                operand.push(c);
+            }
+        }
+
+        operands.retain(|o| !o.is_empty());
+        operands
+    }
+
+    impl Expander {
+        fn error(origin: &Origin, message: String) -> JamParseError {
+            JamParseError::InvalidDirective(message, 0).at(origin)
+        }
+
+        fn push(&mut self, line: String, origin: Origin) {
+            self.out.lines.push(line);
+            self.out.origins.push(origin);
+        }
+
+        /// Replaces constants in the operands of a line, leaving its
+        /// label, mnemonic and `^Label` references alone.
+        fn substitute_constants(&self, text: &str) -> String {
+            let (label, rest) = split_label(text);
+            let rest = rest.trim_start();
+            let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+            let operands = replace_identifiers(operands, |name, reference| {
+                self.constants.get(name).filter(|_| !reference).cloned()
+            });
+
+            match label {
+                Some(label) => format!("{label}: {mnemonic} {operands}"),
+                None => format!("{mnemonic} {operands}"),
+            }
+        }
+
+        /// Handles one line, which is not part of a macro definition.
+        fn line(&mut self, text: &str, origin: Origin) -> Result<(), JamParseError> {
+            let Some(text) = remove_comments_pass(text) else {
+                self.push(String::new(), origin);
+                return Ok(());
+            };
+
+            let (label, rest) = split_label(&text);
+            let mut parts = rest.split_whitespace();
+
+            let Some(first) = parts.next() else {
+                self.push(text.clone(), origin);
+                return Ok(());
+            };
+
+            match first {
+                ".macro" | ".endm" => Err(Self::error(
+                    &origin,
+                    format!("`{first}` is not allowed here"),
+                )),
+
+                ".equ" => {
+                    let (Some(name), Some(value), None) =
+                        (parts.next(), parts.next(), parts.next())
+                    else {
+                        return Err(Self::error(
+                            &origin,
+                            ".equ expects a name and a value".into(),
+                        ));
+                    };
+
+                    if !is_valid_label(name) || Register::from_str(name).is_ok() {
+                        return Err(Self::error(
+                            &origin,
+                            format!("invalid constant name `{name}`"),
+                        ));
+                    }
+
+                    let value = self.constants.get(value).map_or(value, String::as_str);
+                    let value = Instruction::parse_immediate::<i64>(value)
+                        .map_err(|e| Self::error(&origin, e.to_string()))?;
+
+                    self.constants.insert(name.to_string(), value.to_string());
+
+                    if let Some(label) = label {
+                        self.push(format!("{label}:"), origin);
+                    }
+
+                    Ok(())
+                }
+
+                name if self.macros.contains_key(name) => {
+                    if let Some(label) = label {
+                        self.push(format!("{label}:"), origin.clone());
+                    }
+
+                    let args = rest.trim_start()[name.len()..].to_string();
+                    self.expand(name, &split_operands(&args), &origin)
+                }
+
+                _ => {
+                    let text = self.substitute_constants(&text);
+                    self.push(text, origin);
+                    Ok(())
+                }
+            }
+        }
+
+        /// Expands a call of the macro `name` made at `origin`.
+        fn expand(
+            &mut self,
+            name: &str,
+            args: &[String],
+            origin: &Origin,
+        ) -> Result<(), JamParseError> {
+            if origin.calls.len() >= MAX_MACRO_DEPTH {
+                return Err(Self::error(
+                    origin,
+                    format!("macro `{name}` nested too deeply"),
+                ));
+            }
+
+            let Some(definition) = self.macros.get(name).cloned() else {
+                return Ok(());
+            };
+
+            if args.len() != definition.params.len() {
+                let message = format!(
+                    "macro `{name}` expects {} arguments, got {}",
+                    definition.params.len(),
+                    args.len()
+                );
+                return Err(Self::error(origin, message));
+            }
+
+            self.expansions += 1;
+            let expansion = self.expansions;
+
+            // Labels defined in the body are local to each expansion.
+            let locals: Vec<&str> = definition
+                .body
+                .iter()
+                .filter_map(|(_, text)| split_label(text).0)
+                .collect();
+
+            for (line, text) in &definition.body {
+                let text = replace_identifiers(text, |identifier, _| {
+                    if let Some(index) = definition.params.iter().position(|p| p == identifier) {
+                        return Some(args[index].clone());
+                    }
+
+                    locals
+                        .contains(&identifier)
+                        .then(|| format!("__{name}_{expansion}_{identifier}"))
+                });
+
+                let mut calls = vec![(name.to_string(), origin.line)];
+                calls.extend(origin.calls.iter().cloned());
+
+                self.line(&text, Origin { line: *line, calls })?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Expands `.equ` constants and `.macro` definitions. A constant can
+    /// be used in place of any number after it is defined. A macro is
+    /// called like an instruction, its parameters are replaced by the
+    /// arguments and the labels it defines are local to each call.
+    ///
+    /// ```text
+    /// .equ Halt $F0
+    /// .macro PushImm value reg
+    ///     Push value
+    ///     Pop reg
+    /// .endm
+    /// PushImm 10 A
+    /// Signal Halt
+    /// ```
+    pub fn expand_macros_pass(lines: &[String]) -> Result<Expansion, JamParseError> {
+        let mut expander = Expander::default();
+        let mut lines = lines.iter().enumerate();
+
+        while let Some((index, text)) = lines.next() {
+            let origin = Origin {
+                line: index,
+                calls: Vec::new(),
+            };
+
+            let stripped = remove_comments_pass(text).unwrap_or_default();
+            let mut parts = stripped.split_whitespace();
+
+            if parts.next() != Some(".macro") {
+                expander.line(text, origin)?;
+                continue;
+            }
+
+            let Some(name) = parts.next() else {
+                return Err(Expander::error(&origin, ".macro expects a name".into()));
+            };
+
+            if !is_valid_label(name) || Instruction::operand_types(name).is_some() {
+                return Err(Expander::error(
+                    &origin,
+                    format!("invalid macro name `{name}`"),
+                ));
+            }
+
+            let params: Vec<String> = split_operands(&parts.collect::<Vec<_>>().join(" "));
+            let mut body = Vec::new();
+
+            loop {
+                let Some((line, text)) = lines.next() else {
+                    return Err(Expander::error(
+                        &origin,
+                        format!("macro `{name}` is missing `.endm`"),
+                    ));
+                };
+
+                let stripped = remove_comments_pass(text).unwrap_or_default();
+                match stripped.split_whitespace().next() {
+                    Some(".endm") => break,
+                    Some(".macro") => {
+                        let origin = Origin {
+                            line,
+                            calls: Vec::new(),
+                        };
+                        return Err(Expander::error(&origin, "macros can not be nested".into()));
+                    }
+                    _ => body.push((line, stripped)),
+                }
+            }
+
+            expander
+                .macros
+                .insert(name.to_string(), Macro { params, body });
+        }
+
+        Ok(expander.out)
+    }
+}
+
+pub mod forms {
+    use std::str::FromStr;
+
+    use strawberryvm::prelude::{Indexed, Instruction};
+
+    use crate::parsing::JamParseError;
+    use crate::passes::labels::Labels;
+
+    /// Picks the form of an instruction that matches its operands, see
+    /// `addressing_pass`, `immediate_pass` and `wide_pass`.
+    pub fn forms_pass(text: &str, labels: &Labels, line: usize) -> Result<String, JamParseError> {
+        Ok(wide_pass(
+            &immediate_pass(&addressing_pass(text, line)?),
+            labels,
+        ))
+    }
+
+    /// Removes whitespace inside brackets, so that `[BP + 2]` is
+    /// a single operand.
+    fn join_brackets(text: &str) -> String {
+        let mut depth = 0;
+
+        text.chars()
+            .filter(|c| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => (),
+                }
+
+                depth == 0 || !c.is_whitespace()
+            })
+            .collect()
+    }
+
+    /// Picks the form of a memory instruction that matches its bracket
+    /// operand. `Load A [BP+2]` becomes `LoadOff A [BP+2]`, `Load A [B+C]`
+    /// becomes `LoadIdx A [B+C]` and `Load A [B]` is the plain `Load A B`.
+    /// Lines without brackets are left alone, brackets on an instruction
+    /// without a memory form such as `Add A [B]` are an error.
+    pub fn addressing_pass(text: &str, line: usize) -> Result<String, JamParseError> {
+        let text = join_brackets(text);
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        let Some((mnemonic, operands)) = parts.split_first() else {
+            return Ok(text);
+        };
+
+        let Some(index) = operands.iter().position(|o| o.starts_with('[')) else {
+            return Ok(text);
+        };
+
+        let operand = operands[index];
+        let Some(inner) = operand
+            .strip_prefix('[')
+            .and_then(|o| o.strip_suffix(']'))
+            .filter(|inner| !inner.is_empty())
+        else {
+            return Err(JamParseError::InvalidOpCode(
+                format!("unterminated memory operand `{operand}`"),
+                line,
+            ));
+        };
+
+        let plain = Instruction::operand_types(mnemonic).and_then(|t| t.get(index).copied());
+        let indirect = Instruction::operand_types(&format!("{mnemonic}Off")).is_some();
+
+        let (form, operand) = match plain {
+            Some("Offset" | "Indexed") => return Ok(text),
+            Some("Register") if indirect && !inner.contains(['+', '-']) => {
+                ((*mnemonic).to_string(), inner)
+            }
+            Some("Register") if indirect && Indexed::from_str(operand).is_ok() => {
+                (format!("{mnemonic}Idx"), operand)
+            }
+            Some("Register") if indirect => (format!("{mnemonic}Off"), operand),
+            _ => {
+                return Err(JamParseError::InvalidOpCode(
+                    format!("`{mnemonic}` does not take a memory operand `{operand}`"),
+                    line,
+                ))
+            }
+        };
+
+        let mut rewritten = vec![form.as_str()];
+        rewritten.extend(&operands[..index]);
+        rewritten.push(operand);
+        rewritten.extend(&operands[index + 1..]);
+
+        Ok(rewritten.join(" "))
+    }
+
+    /// Picks the immediate form of an ALU instruction when its second
+    /// operand is a number, `Add A 1` becomes `AddI A 1`.
+    pub fn immediate_pass(text: &str) -> String {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        let Some((mnemonic, operands)) = parts.split_first() else {
+            return text.to_string();
+        };
+
+        let plain = Instruction::operand_types(mnemonic).unwrap_or(&[]);
+        let form = format!("{mnemonic}I");
+        let immediate = Instruction::operand_types(&form).unwrap_or(&[]);
+
+        let numeric = operands.iter().enumerate().any(|(index, operand)| {
+            plain.get(index) == Some(&"Register")
+                && immediate.get(index) == Some(&"Imm4")
+                && Instruction::parse_immediate::<i64>(operand).is_ok()
+        });
+
+        if !numeric {
+            return text.to_string();
+        }
+
+        let mut rewritten = vec![form.as_str()];
+        rewritten.extend(operands);
+
+        rewritten.join(" ")
+    }
+
+    /// Picks the wide form of an instruction when a number or the
+    /// address of a label does not fit its 8-bit immediate, `Push $1234`
+    /// becomes `PushWide $1234`. Labels missing from `labels` are
+    /// treated as fitting.
+    pub fn wide_pass(text: &str, labels: &Labels) -> String {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        let Some((mnemonic, operands)) = parts.split_first() else {
+            return text.to_string();
+        };
+
+        let plain = Instruction::operand_types(mnemonic).unwrap_or(&[]);
+        let form = format!("{mnemonic}Wide");
+        let wide = Instruction::operand_types(&form).unwrap_or(&[]);
+
+        let overflows = operands.iter().enumerate().any(|(index, operand)| {
+            plain.get(index) == Some(&"u8")
+                && wide.get(index) == Some(&"u16")
+                && match operand.strip_prefix('^') {
+                    Some(name) => labels.get(name).is_some_and(|address| *address > 0xFF),
+                    None => Instruction::parse_immediate::<i64>(operand).is_ok_and(|v| v > 0xFF),
+                }
+        });
+
+        if !overflows {
+            return text.to_string();
+        }
+
+        let mut rewritten = vec![form.as_str()];
+        rewritten.extend(operands);
+
+        rewritten.join(" ")
+    }
+}
+
+pub mod labels {
+    use std::collections::HashMap;
+
+    use strawberryvm::prelude::Instruction;
+
+    use crate::directives::Directive;
+    use crate::parsing::JamParseError;
+    use crate::passes::forms::forms_pass;
+    use crate::passes::pre::remove_comments_pass;
+
+    /// Label names mapped to the address they were defined at.
+    pub type Labels = HashMap<String, u16>;
+
+    pub fn is_valid_label(name: &str) -> bool {
+        let mut chars = name.chars();
+
+        match chars.next() {
+            Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        }
+    }
+
+    /// Splits a `Name:` definition off the front of a line, returning
+    /// the label (if any) and the rest of the line.
+    pub fn split_label(text: &str) -> (Option<&str>, &str) {
+        let trimmed = text.trim_start();
+        let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+
+        match trimmed[..end].strip_suffix(':') {
+            Some(label) => (Some(label), &trimmed[end..]),
+            None => (None, text),
+        }
+    }
+
+    /// Gives every pending label the address, in the order defined.
+    fn define(
+        labels: &mut Labels,
+        pending: &mut Vec<(String, usize)>,
+        address: u16,
+    ) -> Result<(), JamParseError> {
+        for (label, index) in pending.drain(..) {
+            if labels.insert(label.clone(), address).is_some() {
+                return Err(JamParseError::DuplicateLabel(label, index));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pass 1, walks every line and records the address
+    /// each label is defined at. Instructions following odd-sized data
+    /// are moved to the next even address, and so are their labels. A
+    /// label on a line of its own belongs to the line after it, a label
+    /// on an `.org` line to the new address.
+    ///
+    /// Instructions such as `Push ^Label` take their wide form when the
+    /// label is above 255, which moves the labels after them, so lines
+    /// are laid out again until the addresses settle. Addresses only
+    /// grow, so every layout but the last widens another line.
+    pub fn collect_labels_pass(lines: &[String]) -> Result<Labels, JamParseError> {
+        let mut labels = layout_pass(lines, &Labels::new())?;
+
+        for _ in 0..lines.len() {
+            let next = layout_pass(lines, &labels)?;
+
+            if next == labels {
+                break;
+            }
+
+            labels = next;
+        }
+
+        Ok(labels)
+    }
+
+    /// Lays out every line once, picking instruction forms with the
+    /// labels of the previous layout.
+    fn layout_pass(lines: &[String], known: &Labels) -> Result<Labels, JamParseError> {
+        let mut labels = Labels::new();
+        let mut pending = Vec::new();
+        let mut address: u16 = 0;
+
+        for (index, line) in lines.iter().enumerate() {
+            let Some(text) = remove_comments_pass(line) else {
+                continue;
+            };
+
+            let (label, rest) = split_label(&text);
+
+            if let Some(label) = label {
+                if !is_valid_label(label) {
+                    return Err(JamParseError::InvalidLabel(label.to_string(), index));
+                }
+
+                pending.push((label.to_string(), index));
+            }
+
+            let directive = Directive::parse(rest, index)?;
+            let instruction_size = match directive {
+                Some(_) => None,
+                None => forms_pass(rest, known, index)?
+                    .split_whitespace()
+                    .next()
+                    .map(|mnemonic| Instruction::mnemonic_size(mnemonic).unwrap_or(2)),
+            };
+
+            if directive.is_none() && instruction_size.is_none() {
+                continue;
+            }
+
+            match directive {
+                Some(Directive::Org(org)) => address = org,
+                Some(_) => (),
+                None => address = address.wrapping_add(address % 2),
+            }
+
+            define(&mut labels, &mut pending, address)?;
+
+            address = address.wrapping_add(match directive {
+                Some(directive) => directive.size() as u16,
+                None => instruction_size.unwrap_or(0),
+            });
+        }
+
+        define(&mut labels, &mut pending, address)?;
+
+        Ok(labels)
+    }
+
+    /// Pass 2, replaces every `^Name` operand with its value. Operands
+    /// of type `i8` become an offset in instructions relative to the next
+    /// instruction, `u8` and `u16` operands get the absolute address, as
+    /// does the offset of a memory operand such as `[A+^Table]`.
+    pub fn resolve_labels_pass(
+        text: &str,
+        address: u16,
+        labels: &Labels,
+        line: usize,
+    ) -> Result<String, JamParseError> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        let Some((mnemonic, operands)) = parts.split_first() else {
+            return Ok(String::new());
+        };
+
+        let types = Instruction::operand_types(mnemonic).unwrap_or(&[]);
+        let mut resolved = vec![(*mnemonic).to_string()];
+
+        for (index, operand) in operands.iter().enumerate() {
+            if let Some((before, name)) = operand.split_once('^') {
+                if types.get(index) == Some(&"Offset") {
+                    let name = name.trim_end_matches(']');
+                    let target = *labels
+                        .get(name)
+                        .ok_or_else(|| JamParseError::UndefinedLabel(name.to_string(), line))?;
+
+                    resolved.push(format!("{before}{target}]"));
+                    continue;
+                }
+            }
+
+            let Some(name) = operand.strip_prefix('^') else {
+                resolved.push((*operand).to_string());
+                continue;
+            };
+
+            let target = *labels
+                .get(name)
+                .ok_or_else(|| JamParseError::UndefinedLabel(name.to_string(), line))?;
+
+            let out_of_range = || JamParseError::LabelOutOfRange(name.to_string(), line);
+
+            let size = Instruction::mnemonic_size(mnemonic).unwrap_or(2);
+
+            let value = match types.get(index) {
+                Some(&"i8") => {
+                    let next = i32::from(address) + i32::from(size);
+                    let distance = i32::from(target) - next;
+
+                    if distance % 2 != 0 {
+                        return Err(JamParseError::MisalignedLabel(name.to_string(), line));
+                    }
+
+                    i8::try_from(distance / 2).map_err(|_| out_of_range())?.to_string()
+                }
+
+                Some(&"u8") => u8::try_from(target).map_err(|_| out_of_range())?.to_string(),
+
+                Some(&"u16") => target.to_string(),
+
+                _ => {
+                    return Err(JamParseError::InvalidOpCode(
+                        format!("label `^{name}` is not allowed here"),
+                        line,
+                    ))
+                }
+            };
+
+            resolved.push(value);
+        }
+
+        Ok(resolved.join(" "))
+    }
 }
 
 pub mod post {}
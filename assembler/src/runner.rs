@@ -1,18 +1,81 @@
 use strawberryvm::prelude::*;
+use strawberryvm::snapshot::Snapshot;
 
 use crate::signals::apply_signals;
 
-/// Usage: ./machine <prog.bin>
-pub fn run(bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
-    let mut vm = Machine::new();
+/// Creates a machine with the standard signals and loads
+/// the program into it at address 0, decoding it ahead of time.
+pub fn load(bytes: &[u8], memory_size: usize) -> Result<Machine, Box<dyn std::error::Error>> {
+    let mut vm = Machine::builder().memory_size(memory_size).build()?;
 
     apply_signals(&mut vm);
 
     vm.memory.load(bytes, 0)?;
+    vm.predecode(0, bytes.len());
+
+    Ok(vm)
+}
+
+/// Creates a machine with the standard signals in the state
+/// saved by a snapshot.
+pub fn load_state(snapshot: &Snapshot) -> Result<Machine, Box<dyn std::error::Error>> {
+    let mut vm = Machine::builder()
+        .memory_size(snapshot.memory.len())
+        .build()?;
+
+    apply_signals(&mut vm);
+
+    vm.restore(snapshot)?;
+    vm.enable_decode_cache();
+
+    Ok(vm)
+}
+
+/// Usage: ./machine <prog.bin>
+///
+/// Loads the program and runs it with `run_machine`.
+pub fn run(
+    bytes: &[u8],
+    memory_size: usize,
+    max_cycles: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut vm = load(bytes, memory_size)?;
+    run_machine(&mut vm, max_cycles)
+}
 
-    while !vm.machine_halted {
-        vm.step()?;
+/// Runs until the machine halts, or until `max_cycles` cycles have
+/// been spent when a limit is given. When the machine faults or runs
+/// out of cycles its registers are printed to stderr and an error
+/// is returned.
+pub fn run_machine(
+    vm: &mut Machine,
+    max_cycles: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match vm.run_for(max_cycles.unwrap_or(u64::MAX)) {
+        Ok(RunOutcome::Halted) => Ok(()),
+
+        Ok(RunOutcome::BudgetExhausted) => {
+            eprintln!("{}", vm.status());
+            Err(format!(
+                "{} after {} cycles",
+                RunOutcome::BudgetExhausted,
+                vm.cycles()
+            )
+            .into())
+        }
+
+        Err(e) => {
+            eprintln!("{}", vm.status());
+            Err(e.into())
+        }
     }
+}
 
-    Ok(())
+/// Prints an error returned by `run`, describing the faulting
+/// instruction when the machine faulted.
+pub fn report(e: &(dyn std::error::Error + 'static)) {
+    match e.downcast_ref::<VmError>() {
+        Some(e) => eprintln!("Runtime error: {e}"),
+        None => eprintln!("Error: {e}"),
+    }
 }
@@ -1,24 +1,32 @@
-use strawberryvm::prelude::{Machine, Register};
+use strawberryvm::bus::Console;
+use strawberryvm::prelude::{Machine, SignalAction, SignalResult};
 
-fn sig_halt(vm: &mut Machine) {
-    vm.machine_halted = true;
+fn sig_halt(_: &mut Machine) -> SignalResult {
+    Ok(SignalAction::Halt)
 }
 
-fn log_reg_a(vm: &mut Machine) {
-    println!("A = {}", vm.get_register(Register::A));
+fn log_reg_a(vm: &mut Machine) -> SignalResult {
+    println!("A = {}", vm.signal_args().a);
+    Ok(SignalAction::Continue)
 }
 
-fn log_regs(vm: &mut Machine) {
+fn log_regs(vm: &mut Machine) -> SignalResult {
     println!("{}", vm.status());
+    Ok(SignalAction::Continue)
 }
 
-fn mem_dump(vm: &mut Machine) {
+fn mem_dump(vm: &mut Machine) -> SignalResult {
     println!("{}", vm.memory.dump());
+    Ok(SignalAction::Continue)
 }
 
+/// Defines the standard signals, and the console signals
+/// (`0xE0`-`0xE3`) on the host's stdin and stdout.
 pub fn apply_signals(vm: &mut Machine) {
     vm.define_handler(0xF0, sig_halt);
     vm.define_handler(0xF1, log_reg_a);
     vm.define_handler(0xF2, log_regs);
     vm.define_handler(0xF3, mem_dump);
+
+    Console::stdio().install_signals(vm);
 }
@@ -1,7 +1,7 @@
 //! This crate is simply used for [StrawberryVM](https://crates.io/crates/strawberryvm).
 //!
 //! It implements these macros:
-//! - `FromU8` for automatically implementing from u8 traits
+//! - `FromU8` for automatically implementing `TryFrom<u8>`
 //! - `VmInstruction` for creating encode functions, implementing traits, etc.
 //!
 //! It is not intended to be used outside of the
@@ -66,170 +66,263 @@ fn variant_opcode_value(v: &syn::Variant) -> u8 {
     0
 }
 
+/// Memory operands such as `[reg+imm]` are made of several fields,
+/// which are laid out one after another as if they were operands.
+/// Returns the name and type of each field, or `None` for a plain
+/// operand.
+fn operand_fields(ty: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match ty {
+        "Offset" => Some(&[("base", "Register"), ("offset", "u16")]),
+        "Indexed" => Some(&[("base", "Register"), ("index", "Register")]),
+        _ => None,
+    }
+}
+
+/// Width in bits of an operand of the given type once encoded.
+fn operand_width(ty: &str) -> u32 {
+    match ty {
+        "Register" | "Imm4" => 4,
+        "u8" | "i8" => 8,
+        "u16" | "i16" => 16,
+        _ => panic!("Invalid operand type {ty}"),
+    }
+}
+
+/// Lays operands out after the opcode byte. Operands that would
+/// straddle the first word are moved into a second (extension) word.
+fn operand_positions(types: &[&str]) -> Vec<u32> {
+    let mut position = 8;
+    let mut positions = Vec::new();
+
+    for ty in types {
+        let width = operand_width(ty);
+
+        if position < 16 && position + width > 16 {
+            position = 16;
+        }
+
+        positions.push(position);
+        position += width;
+    }
+
+    assert!(position <= 32, "Operands {types:?} do not fit in two words");
+
+    positions
+}
+
+fn encode_operand(ty: &str, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    match ty {
+        "Register" | "u8" | "u16" => quote! { (#value as u32) },
+        "i8" => quote! { ((#value as u8) as u32) },
+        "i16" => quote! { ((#value as u16) as u32) },
+        "Imm4" => quote! { (#value.get() as u32) },
+        _ => unreachable!(),
+    }
+}
+
+fn decode_operand(ty: &str, bits: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    match ty {
+        "Register" => quote! {
+            Register::try_from(#bits as u8).map_err(DecodeError::BadRegister)?
+        },
+        "u8" => quote! { (#bits as u8) },
+        "i8" => quote! { ((#bits as u8) as i8) },
+        "u16" => quote! { (#bits as u16) },
+        "i16" => quote! { ((#bits as u16) as i16) },
+        "Imm4" => quote! { Imm4(#bits as u8) },
+        _ => unreachable!(),
+    }
+}
+
+fn parse_operand(ty: &str, part: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    match ty {
+        "Register" => quote! {
+            Register::from_str(#part).map_err(|x| Self::Err::Fail(x.to_string()))?
+        },
+        _ if operand_fields(ty).is_some() => {
+            let ty = syn::Ident::new(ty, proc_macro2::Span::call_site());
+            quote! {
+                <#ty as std::str::FromStr>::from_str(#part).map_err(Self::Err::Fail)?
+            }
+        }
+        _ => {
+            let ty = syn::Ident::new(ty, proc_macro2::Span::call_site());
+            quote! {
+                Self::parse_immediate::<#ty>(#part).map_err(|x| Self::Err::Fail(x.to_string()))?
+            }
+        }
+    }
+}
+
 fn impl_opcode_struct(ast: &ItemEnum) -> TokenStream {
     let mut field_encodings = Vec::new();
     let mut field_decodings = Vec::new();
     let mut field_to_string = Vec::new();
     let mut field_from_str = Vec::new();
+    let mut field_operand_types = Vec::new();
+    let mut field_sizes = Vec::new();
+    let mut wide_opcodes = Vec::new();
 
     for x in ast.variants.iter() {
         let name = &x.ident;
         let opcode = variant_opcode_value(x);
 
-        if let syn::Fields::Unit = &x.fields {
-            field_encodings.push(quote! {
-                Self::#name => #opcode as u16
-            });
+        let types: Vec<String> = match &x.fields {
+            syn::Fields::Unit => Vec::new(),
+            syn::Fields::Unnamed(fields) => fields
+                .unnamed
+                .iter()
+                .map(|f| get_type_name(&f.ty))
+                .collect(),
+            syn::Fields::Named(_) => panic!("Unknown fields type for ident {name}"),
+        };
 
-            field_decodings.push(quote! {
-                #opcode => Ok(Self::#name)
-            });
+        let types: Vec<&str> = types.iter().map(AsRef::as_ref).collect();
 
-            field_to_string.push(quote! {
-                Self::#name => write!(f, stringify!(#name))
-            });
+        // Every encoded value with the operand it belongs to and, for
+        // memory operands, the name of its field.
+        let parts: Vec<(usize, Option<&str>, &str)> = types
+            .iter()
+            .enumerate()
+            .flat_map(|(i, ty)| match operand_fields(ty) {
+                Some(fields) => fields.iter().map(|(f, t)| (i, Some(*f), *t)).collect(),
+                None => vec![(i, None, *ty)],
+            })
+            .collect();
 
-            field_from_str.push(quote! {
-                    stringify!(#name) => {
-                        Instruction::assert_length(&parts, 1).map_err(|x| Self::Err::Fail(x.to_string()))?;
-                        Ok(Self::#name)
-                    }
-                });
+        let part_types: Vec<&str> = parts.iter().map(|(_, _, ty)| *ty).collect();
+        let positions = operand_positions(&part_types);
 
-            continue;
-        }
+        let wide = part_types
+            .iter()
+            .zip(&positions)
+            .any(|(ty, position)| position + operand_width(ty) > 16);
 
-        if let syn::Fields::Unnamed(fields) = &x.fields {
-            let types: Vec<_> = fields
-                .unnamed
-                .iter()
-                .map(|f| get_type_name(&f.ty))
-                .collect();
+        let size: u16 = if wide { 4 } else { 2 };
 
-            let types: Vec<&str> = types.iter().map(AsRef::as_ref).collect();
+        if wide {
+            wide_opcodes.push(quote! { #opcode });
+        }
 
-            match types[..] {
-                ["u8"] => {
-                    field_encodings.push(quote! {
-                        Self::#name(u) => #opcode as u16 | ((*u as u16) << 8)
-                    });
+        let fields: Vec<syn::Ident> = (0..types.len())
+            .map(|i| syn::Ident::new(&format!("f{i}"), proc_macro2::Span::call_site()))
+            .collect();
 
-                    field_decodings.push(quote! {
-                        #opcode => Ok(Self::#name(((ins & 0xff00) >> 8) as u8))
-                    });
+        let pattern = if fields.is_empty() {
+            quote! { Self::#name }
+        } else {
+            quote! { Self::#name(#(#fields),*) }
+        };
 
-                    field_to_string.push(quote! {
-                        Self::#name(b) => write!(f, "{} {}", stringify!(#name), b)
-                    });
+        let encoded: Vec<_> = parts
+            .iter()
+            .zip(&positions)
+            .map(|((i, part, ty), position)| {
+                let field = &fields[*i];
+                let value = match part {
+                    Some(part) => {
+                        let part = syn::Ident::new(part, proc_macro2::Span::call_site());
+                        encode_operand(ty, quote! { #field.#part })
+                    }
+                    None => encode_operand(ty, quote! { (*#field) }),
+                };
+                let mask = (1u64 << operand_width(ty)) - 1;
+                let mask = mask as u32;
 
-                    field_from_str.push(quote! {
-                            stringify!(#name) => {
-                                Instruction::assert_length(&parts, 2).map_err(|x| Self::Err::Fail(x.to_string()))?;
+                quote! { ((#value & #mask) << #position) }
+            })
+            .collect();
 
-                                Ok(Self::#name(Self::parse_numeric(parts[1]).map_err(|x| Self::Err::Fail(x.to_string()))?))
-                            }
-                        });
-                }
+        field_encodings.push(quote! {
+            #pattern => #opcode as u32 #(| #encoded)*
+        });
 
-                ["i8"] => {
-                    field_encodings.push(quote! {
-                        Self::#name(u) => {
-                            let raw_value = u.to_le_bytes();
-                            #opcode as u16 | ((raw_value[0] as u16) << 8)
-                        }
-                    });
-
-                    field_decodings.push(quote! {
-                        #opcode => {
-                            let raw_value = i8::from_le_bytes([((ins & 0xff00) >> 8) as u8]);
-                            Ok(Self::#name(raw_value))
-                        }
-                    });
-
-                    field_to_string.push(quote! {
-                        Self::#name(b) => write!(f, "{} {}", stringify!(#name), b)
-                    });
-
-                    field_from_str.push(quote! {
-                            stringify!(#name) => {
-                                Instruction::assert_length(&parts, 2).map_err(|x| Self::Err::Fail(x.to_string()))?;
-                                Ok(Self::#name(Self::parse_numeric_signed(parts[1]).map_err(|x| Self::Err::Fail(x.to_string()))?))
-                            }
-                        });
+        let mut decoded_parts = parts
+            .iter()
+            .zip(&positions)
+            .map(|((_, part, ty), position)| {
+                let mask = ((1u64 << operand_width(ty)) - 1) as u32;
+                let value = decode_operand(ty, quote! { ((bits >> #position) & #mask) });
+
+                match part {
+                    Some(part) => {
+                        let part = syn::Ident::new(part, proc_macro2::Span::call_site());
+                        quote! { #part: #value }
+                    }
+                    None => value,
                 }
+            });
 
-                ["Register"] => {
-                    field_encodings.push(quote! {
-                        Self::#name(r) => #opcode as u16 | (((*r as u16)&0xf) << 8)
-                    });
-
-                    field_decodings.push(quote! {
-                        #opcode => Ok(Self::#name(Register::from(((ins & 0xf00) >> 8) as u8)))
-                    });
-
-                    field_to_string.push(quote! {
-                        Self::#name(r) => write!(f, "{} {}", stringify!(#name), r)
-                    });
-
-                    field_from_str.push(quote! {
-                            stringify!(#name) => {
-                                Instruction::assert_length(&parts, 2).map_err(|x| Self::Err::Fail(x.to_string()))?;
-                                Ok(Self::#name(Register::from_str(parts[1]).map_err(|x| Self::Err::Fail(x.to_string()))?))
-                            }
-                        })
+        let decoded: Vec<_> = types
+            .iter()
+            .map(|ty| match operand_fields(ty) {
+                Some(fields) => {
+                    let values = decoded_parts.by_ref().take(fields.len());
+                    let ty = syn::Ident::new(ty, proc_macro2::Span::call_site());
+                    quote! { #ty { #(#values),* } }
                 }
+                None => decoded_parts.next().unwrap(),
+            })
+            .collect();
 
-                ["Register", "Register"] => {
-                    field_encodings.push(quote! {
-                        Self::#name(r1, r2) => #opcode as u16 | (((*r1 as u16) & 0xf) << 8)
-                            | (((*r2 as u16) & 0xf) << 12)
-                    });
-
-                    field_decodings.push(quote! {
-                        #opcode => {
-                            let r1 = (ins & 0xf00) >> 8;
-                            let r2 = (ins & 0xf000) >> 12;
-
-                            Ok(Self::#name(Register::from(r1 as u8), Register::from(r2 as u8)))
-                        }
-                    });
-
-                    field_to_string.push(quote! {
-                        Self::#name(r1, r2) => write!(f, "{} {} {}", stringify!(#name), r1, r2)
-                    });
-
-                    field_from_str.push(quote! {
-                            stringify!(#name) => {
-                                Instruction::assert_length(&parts, 3).map_err(|x| Self::Err::Fail(x.to_string()))?;
-
-                                Ok(Self::#name(
-                                        Register::from_str(parts[1]).map_err(|x| Self::Err::Fail(x.to_string()))?,
-                                        Register::from_str(parts[2]).map_err(|x| Self::Err::Fail(x.to_string()))?
-                                        ))
-                            }
-                        });
-                }
+        field_decodings.push(if decoded.is_empty() {
+            quote! { #opcode => Ok(Self::#name) }
+        } else {
+            quote! { #opcode => Ok(Self::#name(#(#decoded),*)) }
+        });
 
-                _ => panic!("Invalid types {types:?}"),
-            }
+        let format = vec!["{}"; types.len() + 1].join(" ");
+        field_to_string.push(quote! {
+            #pattern => write!(f, #format, stringify!(#name) #(, #fields)*)
+        });
+
+        let parsed: Vec<_> = types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| parse_operand(ty, quote! { parts[#i + 1] }))
+            .collect();
+
+        let length = types.len() + 1;
+        let constructed = if parsed.is_empty() {
+            quote! { Self::#name }
         } else {
-            panic!("Unknown fields type for ident {name}");
-        }
+            quote! { Self::#name(#(#parsed),*) }
+        };
+
+        field_from_str.push(quote! {
+            stringify!(#name) => {
+                Instruction::assert_length(&parts, #length).map_err(|x| Self::Err::Fail(x.to_string()))?;
+                Ok(#constructed)
+            }
+        });
+
+        field_operand_types.push(quote! {
+            stringify!(#name) => Some(&[#(#types),*])
+        });
+
+        field_sizes.push(quote! {
+            stringify!(#name) => Some(#size)
+        });
     }
 
+    let wide_arm = if wide_opcodes.is_empty() {
+        quote! {}
+    } else {
+        quote! { #(#wide_opcodes)|* => 4, }
+    };
+
     quote! {
         impl TryFrom<u16> for Instruction {
-            type Error = String;
+            type Error = DecodeError;
 
+            /// Decodes a single word instruction, wide instructions
+            /// need their extension word so use `Instruction::decode`.
             fn try_from(ins: u16) -> Result<Self, Self::Error> {
-                let op = (ins & 0xff) as u8;
-
-                match op {
-                    #(#field_decodings,)*
-                    _ => Err(format!(
-                        "Unknown opcode 0x{op:02X} when decoding instruction 0x{ins:04X}"
-                    )),
+                if Self::encoded_size(ins) > 2 {
+                    return Err(DecodeError::MissingExtension((ins & 0xff) as u8));
                 }
+
+                Self::decode(ins, 0)
             }
         }
 
@@ -259,38 +352,107 @@ fn impl_opcode_struct(ast: &ItemEnum) -> TokenStream {
         }
 
         impl Instruction {
-            pub fn encode_u16(&self) -> u16 {
+            /// Decodes an instruction from its first word and the word
+            /// after it, which is only used by wide instructions.
+            pub fn decode(ins: u16, ext: u16) -> Result<Self, DecodeError> {
+                let op = (ins & 0xff) as u8;
+                let bits = (ins as u32) | ((ext as u32) << 16);
+
+                match op {
+                    #(#field_decodings,)*
+                    _ => Err(DecodeError::UnknownOpcode(op)),
+                }
+            }
+
+            /// Size in bytes of the instruction starting with the word `ins`.
+            pub const fn encoded_size(ins: u16) -> u16 {
+                match (ins & 0xff) as u8 {
+                    #wide_arm
+                    _ => 2,
+                }
+            }
+
+            /// Size in bytes of the instruction with the given mnemonic,
+            /// or `None` if the mnemonic is unknown.
+            pub fn mnemonic_size(mnemonic: &str) -> Option<u16> {
+                match mnemonic {
+                    #(#field_sizes,)*
+                    _ => None,
+                }
+            }
+
+            /// Size in bytes of this instruction once encoded.
+            pub fn size(&self) -> u16 {
+                Self::encoded_size(self.encode_u16())
+            }
+
+            /// Returns the operand type names of the instruction with the
+            /// given mnemonic, or `None` if the mnemonic is unknown.
+            pub fn operand_types(mnemonic: &str) -> Option<&'static [&'static str]> {
+                match mnemonic {
+                    #(#field_operand_types,)*
+                    _ => None,
+                }
+            }
+
+            fn encode_bits(&self) -> u32 {
                 match self {
                     #(#field_encodings,)*
                 }
             }
 
+            /// Encodes the first word of the instruction.
+            pub fn encode_u16(&self) -> u16 {
+                (self.encode_bits() & 0xffff) as u16
+            }
+
+            /// Encodes every word of the instruction, wide
+            /// instructions are followed by an extension word.
+            pub fn encode(&self) -> Vec<u16> {
+                let bits = self.encode_bits();
+
+                if self.size() > 2 {
+                    vec![(bits & 0xffff) as u16, (bits >> 16) as u16]
+                } else {
+                    vec![(bits & 0xffff) as u16]
+                }
+            }
+
             fn extract_num_and_radix(s: &str) -> (&str, u32) {
-    let first = s.chars().next().unwrap();
-    match first {
-        '$' | '%' => (&s[1..], 16),
-        '%' => (&s[1..], 2),
-        _ => (s, 10),
-    }
-}
+                match s.chars().next() {
+                    Some('$') => (&s[1..], 16),
+                    Some('%') => (&s[1..], 2),
+                    _ => (s, 10),
+                }
+            }
+
+            /// Used to parse an immediate of any integer type. Accepts
+            /// decimal, `$` hexadecimal and `%` binary, optionally negative.
+            pub fn parse_immediate<T: TryFrom<i64>>(s: &str) -> Result<T, Box<dyn std::error::Error>> {
+                let (negative, digits) = match s.strip_prefix('-') {
+                    Some(rest) => (true, rest),
+                    None => (false, s),
+                };
 
+                let (num, radix) = Self::extract_num_and_radix(digits);
+                let value = i64::from_str_radix(num, radix).map_err(|x| format!("{x} `{s}`"))?;
+                let value = if negative { -value } else { value };
+
+                T::try_from(value).map_err(|_| format!("Immediate `{s}` is out of range").into())
+            }
 
             /// Used to parse a numeric that is unsigned.
             pub fn parse_numeric(s: &str) -> Result<u8, Box<dyn std::error::Error>> {
-                let (num, radix) = Self::extract_num_and_radix(s);
-
-                Ok(u8::from_str_radix(num, radix)?)
+                Self::parse_immediate(s)
             }
 
             /// Used to parse a numeric that is signed.
             pub fn parse_numeric_signed(s: &str) -> Result<i8, Box<dyn std::error::Error>> {
-                let (num, radix) = Self::extract_num_and_radix(s);
-
-                Ok(i8::from_str_radix(num, radix).map_err(|x| format!("{}", x))?)
+                Self::parse_immediate(s)
             }
 
             pub fn assert_length(parts: &[&str], n: usize) -> Result<(), Box<dyn std::error::Error>> {
-                if !parts.len() == n {
+                if parts.len() != n {
                     return Err(format!("Expected {} got {}", n, parts.len()).into());
                 }
 
@@ -301,8 +463,8 @@ fn impl_opcode_struct(ast: &ItemEnum) -> TokenStream {
     .into()
 }
 
-/// Automatically implements the from u8 trait
-/// for ease of use
+/// Automatically implements `TryFrom<u8>`, giving
+/// back the value if it is not a variant
 #[proc_macro_derive(FromU8)]
 pub fn derive_from_u8(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     impl_derive_from_u8(input)
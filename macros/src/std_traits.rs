@@ -16,11 +16,13 @@ pub fn impl_derive_from_u8(input: proc_macro::TokenStream) -> proc_macro::TokenS
     let variant_values: Vec<_> = variants.iter().enumerate().map(|(i, _)| i as u8).collect();
 
     let expanded = quote! {
-        impl From<u8> for #name {
-            fn from(item: u8) -> Self {
+        impl TryFrom<u8> for #name {
+            type Error = u8;
+
+            fn try_from(item: u8) -> Result<Self, Self::Error> {
                 match item {
-                    #(#variant_values => #name::#variant_names,)*
-                    _ => panic!("Invalid value {} for {}", item, stringify!(#name)),
+                    #(#variant_values => Ok(#name::#variant_names),)*
+                    _ => Err(item),
                 }
             }
         }
@@ -1,7 +1,9 @@
 use jasm::assembler::Assembler;
-use jasm::helpers::DynErr;
+use jasm::helpers::{parse_memory_size, DynErr};
 use jasm::signals::apply_signals;
 
+use std::env::args;
+
 use strawberryvm::prelude::*;
 
 use crate::helpers::get_input;
@@ -11,19 +13,40 @@ mod helpers;
 fn process_input(
     machine: &mut Machine,
     mem_index: u16,
-    dbyte: u16,
+    words: &[u16],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    machine.memory.write_u16(mem_index, dbyte)?;
+    for (offset, dbyte) in (0..).step_by(2).zip(words) {
+        machine.memory.write_u16(mem_index + offset, *dbyte)?;
+    }
+
     machine.step()?;
 
     Ok(())
 }
 
+fn new_machine(memory_size: usize) -> Result<Machine, DynErr> {
+    let mut machine = Machine::builder().memory_size(memory_size).build()?;
+    apply_signals(&mut machine);
+
+    Ok(machine)
+}
+
+/// Usage: repl [-m, --memory <size>]
+fn memory_size() -> Result<usize, DynErr> {
+    let parts: Vec<String> = args().skip(1).collect();
+
+    match parts.as_slice() {
+        [] => Ok(MEMORY_KILO_BYTES * 1024),
+        [flag, size] if flag == "-m" || flag == "--memory" => parse_memory_size(size),
+        _ => Err("Usage: `repl [--memory <size>]`".into()),
+    }
+}
+
 fn main() -> Result<(), DynErr> {
-    let mut machine = Machine::new();
-    let mut mem_index = 0;
+    let memory_size = memory_size()?;
 
-    apply_signals(&mut machine);
+    let mut machine = new_machine(memory_size)?;
+    let mut mem_index = 0;
 
     let assembler = Assembler();
 
@@ -43,11 +66,11 @@ fn main() -> Result<(), DynErr> {
             println!("{}", machine.status());
             println!("-- Restarting VM! --");
             mem_index = 0;
-            machine = Machine::new();
+            machine = new_machine(memory_size)?;
             continue;
         }
 
-        let dbyte = match assembler.parse_line(String::from(input), 0) {
+        let words = match assembler.parse_line(String::from(input), 0) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("Error: {e}");
@@ -55,15 +78,15 @@ fn main() -> Result<(), DynErr> {
             }
         };
 
-        if let Err(e) = process_input(&mut machine, mem_index, dbyte) {
+        if let Err(e) = process_input(&mut machine, mem_index, &words) {
             println!("Failed: {e}");
             println!("{}", machine.status());
             println!("-- Restarting VM! --");
-            machine = Machine::new();
+            machine = new_machine(memory_size)?;
             mem_index = 0;
         };
 
-        mem_index += 2;
+        mem_index += 2 * words.len() as u16;
     }
 
     Ok(())
@@ -0,0 +1,174 @@
+use std::{env::args, process::exit};
+
+use jasm::helpers::parse_memory_size;
+use strawberryvm::prelude::MEMORY_KILO_BYTES;
+
+pub fn usage() {
+    println!(
+        "
+Svm - Strawberry virtual machine
+
+Usage: svm <prog.bin> [options]
+       svm --load-state <state> [options]
+
+-m, --memory  | Memory size of the machine, e.g. 4096, $1000 or 64K (defaults to 1K.)
+-d, --debug   | Step through the program in an interactive debugger.
+--max-cycles  | Stop with an error after this many cycles, not with --debug.
+--save-state  | Write a snapshot of the machine to a file when it stops.
+--load-state  | Resume from a snapshot instead of loading a program.
+--trace       | Write every executed instruction to a file as JSON Lines.
+--profile     | Print the hottest addresses and opcodes when the program stops.
+--folded      | Write the profile's call stacks to a file for flamegraph tools.
+--symbols     | Name addresses in profiles with debug info from jasm --symbols.
+--coverage    | Write line and branch coverage to a file in lcov format, needs --symbols.
+--annotate    | Write the source annotated with how often each line ran, needs --symbols.
+
+Example usages:
+    svm main.bin
+    svm main.bin --memory 64K
+    svm --debug main.bin
+    svm main.bin --max-cycles 100000
+    svm main.bin --max-cycles 100000 --save-state main.state
+    svm --load-state main.state
+    svm main.bin --trace main.jsonl
+    svm main.bin --profile --symbols main.dbg
+    svm main.bin --folded main.folded --symbols main.dbg
+    svm main.bin --symbols main.dbg --coverage main.info --annotate main.cov
+"
+    );
+}
+
+pub struct Arguments {
+    pub input: Option<String>,
+
+    pub memory_size: usize,
+    pub debug: bool,
+    pub max_cycles: Option<u64>,
+
+    pub save_state: Option<String>,
+    pub load_state: Option<String>,
+
+    pub trace: Option<String>,
+
+    pub profile: bool,
+    pub folded: Option<String>,
+    pub symbols: Option<String>,
+
+    pub coverage: Option<String>,
+    pub annotate: Option<String>,
+}
+
+impl Arguments {
+    pub fn parse() -> Self {
+        let parts: Vec<String> = args().skip(1).collect();
+
+        let mut input = None;
+        let mut memory_size = MEMORY_KILO_BYTES * 1024;
+        let mut debug = false;
+        let mut max_cycles = None;
+        let mut save_state = None;
+        let mut load_state = None;
+        let mut trace = None;
+        let mut profile = false;
+        let mut folded = None;
+        let mut symbols = None;
+        let mut coverage = None;
+        let mut annotate = None;
+
+        let mut parts = parts.iter();
+        while let Some(part) = parts.next() {
+            match part.as_str() {
+                "-m" | "--memory" => {
+                    let Some(v) = parts.next() else {
+                        usage();
+                        exit(1);
+                    };
+
+                    memory_size = match parse_memory_size(v) {
+                        Ok(size) => size,
+                        Err(e) => {
+                            eprintln!("Invalid memory size `{v}`: {e}");
+                            exit(1);
+                        }
+                    };
+                }
+
+                "-d" | "--debug" => debug = true,
+
+                "--profile" => profile = true,
+
+                "--max-cycles" => {
+                    let Some(v) = parts.next() else {
+                        usage();
+                        exit(1);
+                    };
+
+                    max_cycles = match v.parse() {
+                        Ok(cycles) => Some(cycles),
+                        Err(e) => {
+                            eprintln!("Invalid cycle count `{v}`: {e}");
+                            exit(1);
+                        }
+                    };
+                }
+
+                "--save-state" | "--load-state" | "--trace" | "--folded" | "--symbols"
+                | "--coverage" | "--annotate" => {
+                    let Some(v) = parts.next() else {
+                        usage();
+                        exit(1);
+                    };
+
+                    let path = Some(v.to_string());
+                    match part.as_str() {
+                        "--save-state" => save_state = path,
+                        "--load-state" => load_state = path,
+                        "--folded" => folded = path,
+                        "--symbols" => symbols = path,
+                        "--coverage" => coverage = path,
+                        "--annotate" => annotate = path,
+                        _ => trace = path,
+                    }
+                }
+
+                flag if flag.starts_with('-') => {
+                    usage();
+                    exit(1);
+                }
+
+                path if input.is_none() => input = Some(path.to_string()),
+
+                _ => {
+                    usage();
+                    exit(1);
+                }
+            }
+        }
+
+        let needs_symbols = coverage.is_some() || annotate.is_some();
+        if input.is_none() == load_state.is_none() || (needs_symbols && symbols.is_none()) {
+            usage();
+            exit(1);
+        }
+
+        if debug && max_cycles.is_some() {
+            eprintln!("`--max-cycles` cannot be used with `--debug`");
+            exit(1);
+        }
+
+        Self {
+            input,
+            memory_size,
+            debug,
+            max_cycles,
+            save_state,
+            load_state,
+            trace,
+            profile,
+            folded,
+            symbols,
+            coverage,
+            annotate,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use jasm::debug_info::DebugInfo;
+use strawberryvm::coverage::{is_branch, Branch, Coverage};
+use strawberryvm::prelude::*;
+
+/// Coverage of one source line.
+#[derive(Default)]
+struct Line {
+    hits: u64,
+    /// Set when the line is a conditional jump, `None` inside when the
+    /// jump never ran.
+    branch: Option<Option<Branch>>,
+}
+
+/// Whether the instruction at `address` is a conditional jump, going by
+/// what is in memory now.
+fn branch_at(vm: &Machine, address: u16) -> bool {
+    let word = vm
+        .memory
+        .peek(address)
+        .zip(vm.memory.peek(address.wrapping_add(1)))
+        .map(|(lower, upper)| u16::from_le_bytes([lower, upper]));
+
+    word.and_then(|word| Instruction::decode(word, 0).ok())
+        .is_some_and(|instruction| is_branch(&instruction))
+}
+
+/// Maps the coverage of each address to the source line it came from.
+fn lines(coverage: &Coverage, info: &DebugInfo, vm: &Machine) -> BTreeMap<usize, Line> {
+    let mut lines: BTreeMap<usize, Line> = BTreeMap::new();
+
+    for (address, number) in &info.lines {
+        let line = lines.entry(*number).or_default();
+        line.hits += coverage.hits(*address);
+
+        if branch_at(vm, *address) {
+            line.branch = Some(coverage.branch(*address));
+        }
+    }
+
+    lines
+}
+
+fn count(branch: Option<Branch>, outcome: fn(Branch) -> u64) -> String {
+    branch.map_or_else(|| "-".to_string(), |b| outcome(b).to_string())
+}
+
+/// An lcov tracefile with line and branch coverage of `source`, for
+/// tools such as `genhtml`. Every conditional jump is a branch with a
+/// taken and a not taken outcome.
+pub fn lcov(coverage: &Coverage, info: &DebugInfo, vm: &Machine, source: &str) -> String {
+    let lines = lines(coverage, info, vm);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "TN:");
+    let _ = writeln!(out, "SF:{source}");
+
+    let (mut found, mut hit) = (0, 0);
+    for (number, line) in &lines {
+        if let Some(branch) = line.branch {
+            let taken = count(branch, |b| b.taken);
+            let not_taken = count(branch, |b| b.not_taken);
+
+            let _ = writeln!(out, "BRDA:{number},0,0,{taken}");
+            let _ = writeln!(out, "BRDA:{number},0,1,{not_taken}");
+
+            found += 2;
+            hit += branch.map_or(0, |b| {
+                usize::from(b.taken > 0) + usize::from(b.not_taken > 0)
+            });
+        }
+    }
+
+    let _ = writeln!(out, "BRF:{found}");
+    let _ = writeln!(out, "BRH:{hit}");
+
+    for (number, line) in &lines {
+        let _ = writeln!(out, "DA:{number},{}", line.hits);
+    }
+
+    let _ = writeln!(out, "LF:{}", lines.len());
+    let _ = writeln!(out, "LH:{}", lines.values().filter(|l| l.hits > 0).count());
+    let _ = writeln!(out, "end_of_record");
+
+    out
+}
+
+/// The source with how many times each line ran in front of it.
+/// Lines that never ran are marked with `#####`, and conditional jumps
+/// say how often they were taken.
+pub fn annotate(coverage: &Coverage, info: &DebugInfo, vm: &Machine, text: &str) -> String {
+    let lines = lines(coverage, info, vm);
+    let mut out = String::new();
+
+    let executed = lines.values().filter(|l| l.hits > 0).count();
+    let _ = writeln!(
+        out,
+        "{executed} of {} lines executed ({:.1}%)\n",
+        lines.len(),
+        executed as f64 * 100.0 / lines.len().max(1) as f64
+    );
+
+    for (index, source) in text.lines().enumerate() {
+        let number = index + 1;
+
+        let (hits, note) = match lines.get(&number) {
+            None => (String::new(), String::new()),
+            Some(line) => {
+                let hits = match line.hits {
+                    0 => "#####".to_string(),
+                    n => n.to_string(),
+                };
+
+                let note = match line.branch {
+                    Some(Some(b)) => format!("  [taken {}, not taken {}]", b.taken, b.not_taken),
+                    _ => String::new(),
+                };
+
+                (hits, note)
+            }
+        };
+
+        let _ = writeln!(out, "{hits:>9} | {number:>5} | {source}{note}");
+    }
+
+    out
+}
@@ -0,0 +1,177 @@
+use std::io::{stdin, stdout, Write};
+
+use strawberryvm::debugger::Debugger;
+use strawberryvm::prelude::*;
+
+const HELP: &str = "
+Commands:
+    s, step [n]          | Execute n instructions (defaults to 1.)
+    c, continue          | Run until a breakpoint, watchpoint or halt.
+    bs, back [n]         | Undo n instructions (defaults to 1.)
+    rw, rewind <addr>    | Go back to just before the last write to an address.
+    b, break <addr>      | Set a breakpoint.
+    d, delete <addr>     | Remove a breakpoint.
+    w, watch <addr>      | Stop when the byte at an address changes.
+    u, unwatch <addr>    | Remove a watchpoint.
+    i, info              | List breakpoints and watchpoints.
+    r, regs              | Show registers and flags.
+    x, mem <start> [end] | Dump memory (defaults to 64 bytes.)
+    l, dis [n]           | Disassemble n instructions around PC (defaults to 5.)
+    h, help              | Show this message.
+    q, quit              | Exit the debugger.
+
+Addresses can be decimal, $hex or %binary.
+";
+
+/// How many instructions can be undone with `back` and `rewind`.
+const JOURNAL_CAPACITY: usize = 100_000;
+
+fn parse_address(s: Option<&str>) -> Result<u16, String> {
+    let s = s.ok_or("Missing address")?;
+    Instruction::parse_immediate::<u16>(s).map_err(|e| e.to_string())
+}
+
+fn parse_count(s: Option<&str>, default: usize) -> Result<usize, String> {
+    s.map_or(Ok(default), |s| {
+        s.parse().map_err(|_| format!("Invalid count `{s}`"))
+    })
+}
+
+fn print_stop(debugger: &Debugger, reason: &impl std::fmt::Display) {
+    println!("{reason}");
+    print!("{}", debugger.disassemble_around_pc(0, 0));
+}
+
+/// Runs one command, returning false when the debugger should exit.
+fn command(debugger: &mut Debugger, line: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut parts = line.split_whitespace();
+    let Some(name) = parts.next() else {
+        return Ok(true);
+    };
+
+    match name {
+        "s" | "step" => {
+            let reason = debugger.step(parse_count(parts.next(), 1)?)?;
+            print_stop(debugger, &reason);
+        }
+
+        "c" | "continue" => {
+            let reason = debugger.run()?;
+            print_stop(debugger, &reason);
+        }
+
+        "bs" | "back" => {
+            let n = parse_count(parts.next(), 1)?;
+            let undone = debugger.step_back(n)?;
+
+            if undone < n {
+                println!("Reached the start of the history");
+            }
+
+            print!("{}", debugger.disassemble_around_pc(0, 0));
+        }
+
+        "rw" | "rewind" => {
+            let address = parse_address(parts.next())?;
+
+            match debugger.rewind_to_write(address)? {
+                Some(steps) => {
+                    println!("Went back {steps} instructions");
+                    print!("{}", debugger.disassemble_around_pc(0, 0));
+                }
+                None => println!("No recorded write to 0x{address:04X}"),
+            }
+        }
+
+        "b" | "break" => {
+            let address = parse_address(parts.next())?;
+            debugger.add_breakpoint(address);
+            println!("Breakpoint set @ 0x{address:04X}");
+        }
+
+        "d" | "delete" => {
+            let address = parse_address(parts.next())?;
+            if !debugger.remove_breakpoint(address) {
+                println!("No breakpoint @ 0x{address:04X}");
+            }
+        }
+
+        "w" | "watch" => {
+            let address = parse_address(parts.next())?;
+            debugger.add_watchpoint(address)?;
+            println!("Watching 0x{address:04X}");
+        }
+
+        "u" | "unwatch" => {
+            let address = parse_address(parts.next())?;
+            if !debugger.remove_watchpoint(address) {
+                println!("No watchpoint @ 0x{address:04X}");
+            }
+        }
+
+        "i" | "info" => {
+            for address in debugger.breakpoints() {
+                println!("Breakpoint @ 0x{address:04X}");
+            }
+
+            for address in debugger.watchpoints() {
+                println!("Watchpoint @ 0x{address:04X}");
+            }
+        }
+
+        "r" | "regs" => {
+            println!("{}", debugger.machine.status());
+            println!(" Flags: {}", debugger.flags().join(" "));
+        }
+
+        "x" | "mem" => {
+            let start = parse_address(parts.next())?;
+            let end = match parts.next() {
+                Some(end) => parse_address(Some(end))?,
+                None => start.saturating_add(63),
+            };
+
+            print!("{}", debugger.memory_dump(start..=end));
+        }
+
+        "l" | "dis" => {
+            let n = parse_count(parts.next(), 5)?;
+            let before = u16::try_from(n).unwrap_or(u16::MAX);
+            print!("{}", debugger.disassemble_around_pc(before, n));
+        }
+
+        "h" | "help" => println!("{HELP}"),
+
+        "q" | "quit" => return Ok(false),
+
+        _ => println!("Unknown command `{name}`, type `help` for a list of commands."),
+    }
+
+    Ok(true)
+}
+
+/// Interactive prompt for stepping through a program. The machine
+/// is given back once the user quits.
+pub fn debug(mut machine: Machine) -> Result<Machine, Box<dyn std::error::Error>> {
+    machine.enable_journal(JOURNAL_CAPACITY);
+    let mut debugger = Debugger::new(machine);
+
+    println!("Type `help` for a list of commands.");
+    print!("{}", debugger.disassemble_around_pc(0, 0));
+
+    loop {
+        print!("(svm) ");
+        stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin().read_line(&mut line)? == 0 {
+            return Ok(debugger.into_inner());
+        }
+
+        match command(&mut debugger, line.trim()) {
+            Ok(true) => (),
+            Ok(false) => return Ok(debugger.into_inner()),
+            Err(e) => println!("Error: {e}"),
+        }
+    }
+}
@@ -1,17 +1,26 @@
-use jasm::runner::run;
+use jasm::debug_info::DebugInfo;
+use jasm::runner::{load, load_state, report, run_machine};
+use strawberryvm::coverage::CoverageRecorder;
+use strawberryvm::profile::Profiler;
+use strawberryvm::snapshot::Snapshot;
+use strawberryvm::trace::{JsonLines, Tee, TraceSink};
 
-use std::env::{self, args};
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::process::exit;
 use std::panic;
 
-fn load_program() -> Vec<u8> {
-    let args: Vec<String> = env::args().collect();
-    assert!(args.len() >= 2);
+use crate::arguments::Arguments;
+use crate::debug::debug;
 
-    let mut file = match File::open(Path::new(&args[1])) {
+mod arguments;
+mod coverage;
+mod debug;
+mod profile;
+
+fn load_program(path: &str) -> Vec<u8> {
+    let mut file = match File::open(Path::new(path)) {
         Ok(file) => file,
         Err(e) => {
             eprintln!("Failed to open file: {e}");
@@ -25,12 +34,106 @@ fn load_program() -> Vec<u8> {
     program
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    if args().len() != 2 {
-        println!("Usage: `svm prog.bin`");
-        exit(1);
+/// Loads or resumes the machine, runs it, and saves its trace, profile,
+/// coverage and state when asked to, even if it faulted.
+fn execute(args: &Arguments) -> Result<(), Box<dyn std::error::Error>> {
+    let mut vm = match (&args.load_state, &args.input) {
+        (Some(path), _) => {
+            let snapshot = Snapshot::read_from(BufReader::new(File::open(path)?))
+                .map_err(|e| format!("Failed to load state from `{path}`: {e}"))?;
+
+            load_state(&snapshot)?
+        }
+
+        (None, Some(input)) => load(&load_program(input), args.memory_size)?,
+        (None, None) => unreachable!("arguments require an input or a state"),
+    };
+
+    let symbols = match &args.symbols {
+        Some(path) => Some(
+            DebugInfo::parse(&std::fs::read_to_string(path)?)
+                .map_err(|e| format!("Failed to load symbols from `{path}`: {e}"))?,
+        ),
+        None => None,
+    };
+
+    let mut sinks: Vec<Box<dyn TraceSink>> = Vec::new();
+
+    if let Some(path) = &args.trace {
+        let file = BufWriter::new(File::create(path)?);
+        sinks.push(Box::new(JsonLines::new(file)));
+    }
+
+    let profiler = (args.profile || args.folded.is_some()).then(Profiler::new);
+    if let Some(profiler) = &profiler {
+        sinks.push(Box::new(profiler.clone()));
+    }
+
+    let recorder = (args.coverage.is_some() || args.annotate.is_some()).then(CoverageRecorder::new);
+    if let Some(recorder) = &recorder {
+        sinks.push(Box::new(recorder.clone()));
+    }
+
+    match sinks.len() {
+        0 => (),
+        1 => vm.set_trace_sink(sinks.remove(0)),
+        _ => vm.set_trace_sink(Box::new(Tee(sinks))),
+    }
+
+    let result = if args.debug {
+        vm = debug(vm)?;
+        Ok(())
+    } else {
+        run_machine(&mut vm, args.max_cycles)
+    };
+
+    if let Some(mut sink) = vm.take_trace_sink() {
+        sink.flush()?;
     }
 
+    if let Some(profiler) = profiler {
+        let profile = profiler.profile();
+
+        if args.profile {
+            eprint!("{}", profile::report(&profile, symbols.as_ref()));
+        }
+
+        if let Some(path) = &args.folded {
+            std::fs::write(path, profile::folded(&profile, symbols.as_ref()))?;
+        }
+    }
+
+    if let (Some(recorder), Some(info)) = (recorder, &symbols) {
+        let coverage = recorder.coverage();
+        let source = info
+            .source
+            .as_deref()
+            .ok_or("The symbols do not name a source file")?;
+
+        if let Some(path) = &args.coverage {
+            std::fs::write(path, coverage::lcov(&coverage, info, &vm, source))?;
+        }
+
+        if let Some(path) = &args.annotate {
+            let text = std::fs::read_to_string(source)
+                .map_err(|e| format!("Failed to read source `{source}`: {e}"))?;
+
+            std::fs::write(path, coverage::annotate(&coverage, info, &vm, &text))?;
+        }
+    }
+
+    if let Some(path) = &args.save_state {
+        let mut file = BufWriter::new(File::create(path)?);
+        vm.snapshot()?.write_to(&mut file)?;
+        file.flush()?;
+    }
+
+    result
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Arguments::parse();
+
     // Note: Panic hook must be `Send + Sync`, so it can't capture `Machine`.
     panic::set_hook(Box::new(|info| {
         eprintln!("Runtime error!");
@@ -41,7 +144,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }));
 
-    run(&load_program())?;
+    if let Err(e) = execute(&args) {
+        report(e.as_ref());
+        exit(1);
+    }
 
     Ok(())
 }
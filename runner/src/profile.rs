@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use jasm::debug_info::DebugInfo;
+use strawberryvm::profile::{Counter, Profile};
+
+/// How many addresses the report lists.
+const HOTTEST: usize = 10;
+
+/// Formats a profile for people to read: the hottest addresses with
+/// their instructions, time per opcode, and time per label when debug
+/// info is available.
+pub fn report(profile: &Profile, symbols: Option<&DebugInfo>) -> String {
+    let mut out = String::new();
+    let total = profile.total();
+    let percent = |cycles: u64| cycles as f64 * 100.0 / total.cycles.max(1) as f64;
+
+    let _ = writeln!(
+        out,
+        "Profile: {} instructions, {} cycles",
+        total.count, total.cycles
+    );
+
+    let _ = writeln!(out, "\nHottest addresses:");
+    let _ = writeln!(
+        out,
+        "{:>10} {:>10} {:>6}  Address  Instruction",
+        "Count", "Cycles", "%"
+    );
+    for (address, stats) in profile.hottest(HOTTEST) {
+        let location =
+            symbols.map_or_else(String::new, |s| format!("  ({})", s.symbolize(address)));
+        let _ = writeln!(
+            out,
+            "{:>10} {:>10} {:>6.2}  ${address:04X}    {}{location}",
+            stats.counter.count,
+            stats.counter.cycles,
+            percent(stats.counter.cycles),
+            stats.instruction,
+        );
+    }
+
+    let _ = writeln!(out, "\nBy opcode:");
+    let _ = writeln!(out, "{:>10} {:>10} {:>6}  Opcode", "Count", "Cycles", "%");
+    for (name, counter) in profile.opcodes() {
+        let _ = writeln!(
+            out,
+            "{:>10} {:>10} {:>6.2}  {name}",
+            counter.count,
+            counter.cycles,
+            percent(counter.cycles),
+        );
+    }
+
+    if let Some(symbols) = symbols {
+        let mut labels: BTreeMap<String, Counter> = BTreeMap::new();
+        for (address, stats) in profile.addresses() {
+            let name = symbols
+                .label_before(address)
+                .map_or_else(|| "(no label)".to_string(), |(name, _)| name.to_string());
+
+            let counter = labels.entry(name).or_default();
+            counter.count += stats.counter.count;
+            counter.cycles += stats.counter.cycles;
+        }
+
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort_by(|(a, x), (b, y)| y.cycles.cmp(&x.cycles).then(a.cmp(b)));
+
+        let _ = writeln!(out, "\nBy label:");
+        let _ = writeln!(out, "{:>10} {:>10} {:>6}  Label", "Count", "Cycles", "%");
+        for (name, counter) in labels {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>10} {:>6.2}  {name}",
+                counter.count,
+                counter.cycles,
+                percent(counter.cycles),
+            );
+        }
+    }
+
+    out
+}
+
+/// The profile's call stacks in the folded format used by flamegraph
+/// tools, with frames named by label when debug info is available.
+pub fn folded(profile: &Profile, symbols: Option<&DebugInfo>) -> String {
+    profile.folded(|address| {
+        symbols.map_or_else(|| format!("${address:04X}"), |s| s.symbolize(address))
+    })
+}
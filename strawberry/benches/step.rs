@@ -0,0 +1,68 @@
+//! Compares running a tight loop through `Machine::step` with and
+//! without the decode cache. Run with `cargo bench -p strawberryvm`.
+
+use std::time::{Duration, Instant};
+
+use strawberryvm::prelude::*;
+
+const ITERATIONS: u16 = 50_000;
+const RUNS: u32 = 10;
+
+// LoadImm A ITERATIONS; LoadImm B 1; LoadImm C 0
+// Loop: Add D A; Xor D B; Sub A B; Cmp A C; Jne ^Loop
+// Signal $F0
+const PROGRAM: &[u16] = &[
+    0x0014, ITERATIONS, 0x0114, 1, 0x0214, 0, 0x0320, 0x1326, 0x1021, 0x2030, 0xFB33, 0xF050,
+];
+
+fn machine(cached: bool) -> Result<Machine, Box<dyn std::error::Error>> {
+    let mut machine = Machine::new();
+    machine.define_handler(0xF0, |_| Ok(SignalAction::Halt));
+
+    for (addr, word) in (0..).step_by(2).zip(PROGRAM) {
+        machine.memory.write_u16(addr, *word)?;
+    }
+
+    if cached {
+        machine.predecode(0, PROGRAM.len() * 2);
+    }
+
+    Ok(machine)
+}
+
+/// Runs the program `RUNS` times, returning the instructions executed
+/// and the time it took.
+fn bench(cached: bool) -> Result<(u32, Duration), Box<dyn std::error::Error>> {
+    let mut instructions = 0;
+    let mut elapsed = Duration::ZERO;
+
+    for _ in 0..RUNS {
+        let mut machine = machine(cached)?;
+
+        let start = Instant::now();
+        while !machine.machine_halted {
+            machine.step()?;
+            instructions += 1;
+        }
+        elapsed += start.elapsed();
+    }
+
+    Ok((instructions, elapsed))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (instructions, uncached) = bench(false)?;
+    let (_, cached) = bench(true)?;
+
+    for (name, elapsed) in [("step", uncached), ("step, decode cache", cached)] {
+        let per_second = f64::from(instructions) / elapsed.as_secs_f64();
+        println!("{name:<20} {elapsed:>12.2?} {per_second:>14.0} instructions/s");
+    }
+
+    println!(
+        "Speedup: {:.2}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+
+    Ok(())
+}
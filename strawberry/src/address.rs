@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use crate::op::Instruction;
+use crate::register::Register;
+
+/// A memory operand `[reg+imm]`, the address is the value of `base`
+/// plus `offset`, wrapping around. `[BP-2]` is a `BP` relative offset
+/// of `0xFFFE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offset {
+    pub base: Register,
+    pub offset: u16,
+}
+
+/// A memory operand `[reg+reg]`, the address is the value of `base`
+/// plus the value of `index`, wrapping around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indexed {
+    pub base: Register,
+    pub index: Register,
+}
+
+impl Offset {
+    /// The address the operand refers to with the given register values.
+    #[must_use]
+    pub const fn address(&self, registers: &[u16]) -> u16 {
+        registers[self.base as usize].wrapping_add(self.offset)
+    }
+}
+
+impl Indexed {
+    /// The address the operand refers to with the given register values.
+    #[must_use]
+    pub const fn address(&self, registers: &[u16]) -> u16 {
+        registers[self.base as usize].wrapping_add(registers[self.index as usize])
+    }
+}
+
+/// Takes the brackets off a memory operand and splits it into the
+/// base register and what is added to it, such as `+2` or `-2`.
+fn split_operand(s: &str) -> Result<(Register, &str), String> {
+    let inner = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("Expected a memory operand such as `[BP+2]`, got `{s}`"))?;
+
+    let (base, rest) = inner.split_at(inner.find(['+', '-']).unwrap_or(inner.len()));
+
+    let base = Register::from_str(base).map_err(|x| format!("{x} `{base}` in `{s}`"))?;
+    Ok((base, rest))
+}
+
+impl FromStr for Offset {
+    type Err = String;
+
+    /// Parses `[reg]`, `[reg+imm]` or `[reg-imm]`, the immediate is any
+    /// numeric `Instruction::parse_immediate` accepts from `-32768` to
+    /// `$FFFF`, so `[BP+-2]` is `[BP-2]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, rest) = split_operand(s)?;
+
+        if rest.is_empty() {
+            return Ok(Self { base, offset: 0 });
+        }
+
+        let imm = rest.strip_prefix('+').unwrap_or(rest);
+        let value = Instruction::parse_immediate::<i32>(imm).map_err(|x| x.to_string())?;
+
+        if !(-0x8000..=0xFFFF).contains(&value) {
+            return Err(format!("Offset `{imm}` is out of range"));
+        }
+
+        let offset = u16::try_from(value.rem_euclid(0x1_0000)).map_err(|x| x.to_string())?;
+        Ok(Self { base, offset })
+    }
+}
+
+impl FromStr for Indexed {
+    type Err = String;
+
+    /// Parses `[reg+reg]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, rest) = split_operand(s)?;
+
+        let index = rest
+            .strip_prefix('+')
+            .ok_or_else(|| format!("Expected an indexed operand such as `[A+B]`, got `{s}`"))?;
+        let index = Register::from_str(index).map_err(|x| format!("{x} `{index}` in `{s}`"))?;
+
+        Ok(Self { base, index })
+    }
+}
+
+impl std::fmt::Display for Offset {
+    /// Offsets with the top bit set are written as negative, as they
+    /// usually are locals below `BP`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[allow(clippy::cast_possible_wrap)]
+        match self.offset as i16 {
+            0 => write!(f, "[{}]", self.base),
+            offset if offset < 0 => write!(f, "[{}-{}]", self.base, offset.unsigned_abs()),
+            offset => write!(f, "[{}+{offset}]", self.base),
+        }
+    }
+}
+
+impl std::fmt::Display for Indexed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}+{}]", self.base, self.index)
+    }
+}
@@ -0,0 +1,488 @@
+use std::cell::RefCell;
+use std::io::{Cursor, Read, Write};
+use std::rc::Rc;
+
+use crate::memory::{Addressable, Error, Linear};
+use crate::register::Register;
+use crate::vm::{Machine, SignalAction};
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// A peripheral that can be attached to a `Bus`. Offsets passed to
+/// the device are relative to the address it was attached at.
+pub trait Device {
+    /// Short name shown in memory dumps.
+    fn name(&self) -> &'static str;
+
+    /// Amount of bytes the device takes up on the bus.
+    fn size(&self) -> usize;
+
+    /// Reads a byte from the device.
+    ///
+    /// # Errors
+    /// Fails if the device can not be read at this offset.
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr>;
+
+    /// Writes a byte to the device.
+    ///
+    /// # Errors
+    /// Fails if the device can not be written at this offset.
+    fn write(&mut self, offset: u16, value: u8) -> Result<(), DynErr>;
+
+    /// Reads a byte without side effects, `None` if the device can't.
+    fn peek(&self, _offset: u16) -> Option<u8> {
+        None
+    }
+
+    /// Called once for every instruction the machine executes.
+    fn tick(&mut self) {}
+
+    /// Contents of the device for memory dumps.
+    fn dump(&self) -> String {
+        String::new()
+    }
+}
+
+struct Mapping {
+    start: u16,
+    end: u16,
+    device: RefCell<Box<dyn Device>>,
+}
+
+/// Memory that routes address ranges to attached devices. It can be
+/// used as the memory of a `Machine` so that programs talk to devices
+/// with plain `Load` and `Store` instructions.
+///
+/// ```rust
+/// use strawberryvm::prelude::*;
+/// use strawberryvm::bus::{Bus, Timer};
+///
+/// let mut bus = Bus::new();
+/// bus.attach(0x0000, Linear::new(0x1000)).unwrap();
+/// bus.attach(0xFF00, Timer::new()).unwrap();
+///
+/// let vm = Machine::with_memory(Box::new(bus));
+/// ```
+#[derive(Default)]
+pub struct Bus {
+    mappings: Vec<Mapping>,
+}
+
+impl Bus {
+    /// Creates an empty bus, every address is unmapped.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Attaches a device starting at `start`.
+    ///
+    /// # Errors
+    /// Fails if the device is empty, does not fit in the address space,
+    /// or overlaps a device that is already attached.
+    pub fn attach(&mut self, start: u16, device: impl Device + 'static) -> Result<(), DynErr> {
+        let name = device.name();
+
+        let end = device
+            .size()
+            .checked_sub(1)
+            .and_then(|last| u16::try_from(last).ok())
+            .and_then(|last| start.checked_add(last))
+            .ok_or_else(|| format!("{name} does not fit at 0x{start:04X}"))?;
+
+        if let Some(other) = self
+            .mappings
+            .iter()
+            .find(|m| start <= m.end && m.start <= end)
+        {
+            return Err(format!(
+                "{name} at 0x{start:04X}-0x{end:04X} overlaps {} at 0x{:04X}-0x{:04X}",
+                other.device.borrow().name(),
+                other.start,
+                other.end
+            )
+            .into());
+        }
+
+        self.mappings.push(Mapping {
+            start,
+            end,
+            device: RefCell::new(Box::new(device)),
+        });
+
+        Ok(())
+    }
+
+    fn mapping(&self, addr: u16) -> Result<&Mapping, Error> {
+        self.mappings
+            .iter()
+            .find(|m| (m.start..=m.end).contains(&addr))
+            .ok_or(Error::OutOfBounds(addr))
+    }
+}
+
+impl Addressable for Bus {
+    fn dump(&self) -> String {
+        self.mappings
+            .iter()
+            .map(|m| {
+                let device = m.device.borrow();
+                format!(
+                    "{:04X}-{:04X} {}: {}",
+                    m.start,
+                    m.end,
+                    device.name(),
+                    device.dump()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn read(&self, addr: u16) -> Result<u8, DynErr> {
+        let mapping = self.mapping(addr)?;
+        mapping.device.borrow_mut().read(addr - mapping.start)
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        let mapping = self.mapping(addr).ok()?;
+        let device = mapping.device.borrow();
+        device.peek(addr - mapping.start)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Result<(), DynErr> {
+        let mapping = self.mapping(addr)?;
+        mapping.device.borrow_mut().write(addr - mapping.start, value)
+    }
+
+    fn tick(&mut self) {
+        for mapping in &mut self.mappings {
+            mapping.device.get_mut().tick();
+        }
+    }
+}
+
+impl Device for Linear {
+    fn name(&self) -> &'static str {
+        "RAM"
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr> {
+        Addressable::read(self, offset)
+    }
+
+    fn peek(&self, offset: u16) -> Option<u8> {
+        Addressable::peek(self, offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) -> Result<(), DynErr> {
+        Addressable::write(self, offset, value)
+    }
+
+    fn dump(&self) -> String {
+        Addressable::dump(self)
+    }
+}
+
+/// Read only memory, initialised once when it is created.
+pub struct Rom {
+    bytes: Vec<u8>,
+}
+
+impl Rom {
+    /// Creates a ROM holding a copy of `bytes`.
+    #[must_use]
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            bytes: bytes.to_vec(),
+        }
+    }
+}
+
+impl Device for Rom {
+    fn name(&self) -> &'static str {
+        "ROM"
+    }
+
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr> {
+        self.bytes
+            .get(offset as usize)
+            .copied()
+            .ok_or_else(|| Error::OutOfBounds(offset).into())
+    }
+
+    fn peek(&self, offset: u16) -> Option<u8> {
+        self.bytes.get(offset as usize).copied()
+    }
+
+    fn write(&mut self, offset: u16, _value: u8) -> Result<(), DynErr> {
+        Err(format!("Attempted to write to ROM @ offset 0x{offset:X}").into())
+    }
+}
+
+/// An in-memory writer whose contents can still be read after it has
+/// been handed to a `Console`.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Everything written so far.
+    #[must_use]
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Character console. It can be attached to a `Bus` and used
+/// through its ports, or driven with signals (see `install_signals`).
+///
+/// | Offset | Read                                 | Write               |
+/// |--------|--------------------------------------|---------------------|
+/// | 0      | Next input byte (0 when none remain) | Output a byte       |
+/// | 1      | 1 when an input byte is available    | Ignored             |
+pub struct Console {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    peeked: Option<u8>,
+}
+
+impl Console {
+    pub const DATA: u16 = 0;
+    pub const STATUS: u16 = 1;
+
+    /// Outputs the low byte of `A`.
+    pub const SIGNAL_PUTCHAR: u8 = 0xE0;
+    /// Reads a byte into `A`, or `0xFFFF` when no input remains.
+    pub const SIGNAL_GETCHAR: u8 = 0xE1;
+    /// Prints `A` as an unsigned number in base `B` (2 to 36, 0 means 10).
+    pub const SIGNAL_PRINT_NUMBER: u8 = 0xE2;
+    /// Prints the NUL terminated string at the address in `A`.
+    pub const SIGNAL_PRINT_STRING: u8 = 0xE3;
+
+    /// Creates a console reading from `input` and writing to `output`.
+    #[must_use]
+    pub fn new(input: Box<dyn Read>, output: Box<dyn Write>) -> Self {
+        Self {
+            input,
+            output,
+            peeked: None,
+        }
+    }
+
+    /// Creates a console attached to the host's stdin and stdout.
+    #[must_use]
+    pub fn stdio() -> Self {
+        Self::new(Box::new(std::io::stdin()), Box::new(std::io::stdout()))
+    }
+
+    /// Creates a console reading `input` and writing into a buffer
+    /// that is returned alongside it, for tests.
+    #[must_use]
+    pub fn buffered(input: impl Into<Vec<u8>>) -> (Self, SharedBuffer) {
+        let output = SharedBuffer::default();
+        let console = Self::new(
+            Box::new(Cursor::new(input.into())),
+            Box::new(output.clone()),
+        );
+
+        (console, output)
+    }
+
+    /// Defines the `SIGNAL_*` handlers on `vm`, which share this console.
+    ///
+    /// ```rust
+    /// use strawberryvm::prelude::*;
+    /// use strawberryvm::bus::Console;
+    ///
+    /// let mut vm = Machine::new();
+    /// Console::stdio().install_signals(&mut vm);
+    /// ```
+    pub fn install_signals(self, vm: &mut Machine) {
+        let console = Rc::new(RefCell::new(self));
+
+        let c = Rc::clone(&console);
+        vm.define_handler(Self::SIGNAL_PUTCHAR, move |vm| {
+            c.borrow_mut().put(&vm.signal_args().a.to_le_bytes()[..1])?;
+            Ok(SignalAction::Continue)
+        });
+
+        let c = Rc::clone(&console);
+        vm.define_handler(Self::SIGNAL_GETCHAR, move |vm| {
+            let mut console = c.borrow_mut();
+            let byte = console.peek_input()?.map_or(0xFFFF, u16::from);
+            console.peeked = None;
+
+            vm.set_register(Register::A, byte);
+            Ok(SignalAction::Continue)
+        });
+
+        let c = Rc::clone(&console);
+        vm.define_handler(Self::SIGNAL_PRINT_NUMBER, move |vm| {
+            let args = vm.signal_args();
+            let text = format_number(args.a, args.b)?;
+            c.borrow_mut().put(text.as_bytes())?;
+            Ok(SignalAction::Continue)
+        });
+
+        vm.define_handler(Self::SIGNAL_PRINT_STRING, move |vm| {
+            let mut address = vm.signal_args().a;
+            let mut text = Vec::new();
+
+            loop {
+                let byte = vm.memory.read(address)?;
+                if byte == 0 {
+                    break;
+                }
+
+                text.push(byte);
+                address = address
+                    .checked_add(1)
+                    .ok_or("String is not NUL terminated")?;
+            }
+
+            console.borrow_mut().put(&text)?;
+            Ok(SignalAction::Continue)
+        });
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), DynErr> {
+        self.output.write_all(bytes)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn peek_input(&mut self) -> Result<Option<u8>, DynErr> {
+        if self.peeked.is_none() {
+            let mut byte = [0];
+            if self.input.read(&mut byte)? == 1 {
+                self.peeked = Some(byte[0]);
+            }
+        }
+
+        Ok(self.peeked)
+    }
+}
+
+impl Device for Console {
+    fn name(&self) -> &'static str {
+        "Console"
+    }
+
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr> {
+        match offset {
+            Self::DATA => {
+                let byte = self.peek_input()?.unwrap_or(0);
+                self.peeked = None;
+                Ok(byte)
+            }
+
+            Self::STATUS => Ok(u8::from(self.peek_input()?.is_some())),
+
+            _ => Err(Error::OutOfBounds(offset).into()),
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) -> Result<(), DynErr> {
+        match offset {
+            Self::DATA => self.put(&[value]),
+
+            Self::STATUS => Ok(()),
+
+            _ => Err(Error::OutOfBounds(offset).into()),
+        }
+    }
+}
+
+/// Formats `value` in `base`, where a base of 0 means decimal.
+fn format_number(value: u16, base: u16) -> Result<String, DynErr> {
+    let base = match base {
+        0 => 10,
+        2..=36 => u32::from(base),
+        _ => return Err(format!("Invalid base {base}").into()),
+    };
+
+    let mut value = u32::from(value);
+    let mut digits = Vec::new();
+
+    loop {
+        digits.push(char::from_digit(value % base, base).unwrap_or('?'));
+        value /= base;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    Ok(digits.iter().rev().collect())
+}
+
+/// Counts the instructions executed by the machine. Reading gives the
+/// count as a little endian u16 (wrapping), writing resets it to zero.
+#[derive(Default)]
+pub struct Timer {
+    ticks: u16,
+}
+
+impl Timer {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { ticks: 0 }
+    }
+}
+
+impl Device for Timer {
+    fn name(&self) -> &'static str {
+        "Timer"
+    }
+
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr> {
+        self.peek(offset)
+            .ok_or_else(|| Error::OutOfBounds(offset).into())
+    }
+
+    fn peek(&self, offset: u16) -> Option<u8> {
+        self.ticks.to_le_bytes().get(offset as usize).copied()
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) -> Result<(), DynErr> {
+        self.ticks = 0;
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    fn dump(&self) -> String {
+        format!("{}", self.ticks)
+    }
+}
@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::op::Instruction;
+use crate::trace::{TraceRecord, TraceSink};
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// Whether coverage tracks both outcomes of the instruction, which
+/// is the case for conditional jumps.
+#[must_use]
+pub const fn is_branch(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Je(_)
+            | Instruction::Jne(_)
+            | Instruction::Jl(_)
+            | Instruction::Jg(_)
+            | Instruction::Jle(_)
+            | Instruction::Jge(_)
+            | Instruction::Jb(_)
+            | Instruction::Ja(_)
+            | Instruction::Jc(_)
+            | Instruction::Jo(_)
+            | Instruction::Jn(_)
+    )
+}
+
+/// How often a conditional jump went each way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// The addresses a program executed, gathered by a `CoverageRecorder`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    hits: BTreeMap<u16, u64>,
+    branches: BTreeMap<u16, Branch>,
+}
+
+impl Coverage {
+    fn record(&mut self, record: &TraceRecord) {
+        *self.hits.entry(record.pc).or_default() += 1;
+
+        if record.fault.is_some() {
+            return;
+        }
+
+        if is_branch(&record.instruction) {
+            let size = record.instruction.size();
+            let branch = self.branches.entry(record.pc).or_default();
+
+            if record.next_pc == record.pc.wrapping_add(size) {
+                branch.not_taken += 1;
+            } else {
+                branch.taken += 1;
+            }
+        }
+    }
+
+    /// How many times the instruction at `address` ran.
+    #[must_use]
+    pub fn hits(&self, address: u16) -> u64 {
+        self.hits.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Every executed address with how many times it ran, in address order.
+    pub fn executed(&self) -> impl Iterator<Item = (u16, u64)> + '_ {
+        self.hits.iter().map(|(address, hits)| (*address, *hits))
+    }
+
+    /// The outcomes of the conditional jump at `address`, if it ran.
+    #[must_use]
+    pub fn branch(&self, address: u16) -> Option<Branch> {
+        self.branches.get(&address).copied()
+    }
+}
+
+/// A `TraceSink` that builds a `Coverage`. Like `Profiler`, it can be
+/// cloned before being given to a machine to read the coverage afterwards.
+///
+/// ```rust
+/// use strawberryvm::coverage::CoverageRecorder;
+/// use strawberryvm::prelude::*;
+///
+/// let recorder = CoverageRecorder::new();
+///
+/// let mut vm = Machine::new();
+/// vm.set_trace_sink(Box::new(recorder.clone()));
+/// vm.step().unwrap();
+///
+/// assert_eq!(recorder.coverage().hits(0), 1);
+/// ```
+#[derive(Clone, Default)]
+pub struct CoverageRecorder {
+    coverage: Rc<RefCell<Coverage>>,
+}
+
+impl CoverageRecorder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the coverage gathered so far.
+    #[must_use]
+    pub fn coverage(&self) -> Coverage {
+        self.coverage.borrow().clone()
+    }
+}
+
+impl TraceSink for CoverageRecorder {
+    fn record(&mut self, record: &TraceRecord) -> Result<(), DynErr> {
+        self.coverage.borrow_mut().record(record);
+        Ok(())
+    }
+}
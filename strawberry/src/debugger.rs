@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+use crate::op::Instruction;
+use crate::register::{Flag, Register};
+use crate::vm::Machine;
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// Why the debugger handed control back to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Every requested step was executed.
+    Stepped,
+    /// The machine halted.
+    Halted,
+    /// The program counter reached a breakpoint.
+    Breakpoint(u16),
+    /// A watched byte of memory changed.
+    Watchpoint { address: u16, old: u8, new: u8 },
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Stepped => write!(f, "Stepped"),
+            Self::Halted => write!(f, "Machine halted"),
+            Self::Breakpoint(address) => write!(f, "Breakpoint @ 0x{address:04X}"),
+            Self::Watchpoint { address, old, new } => {
+                write!(f, "Watchpoint @ 0x{address:04X} changed 0x{old:02X} -> 0x{new:02X}")
+            }
+        }
+    }
+}
+
+/// Steps a machine while checking breakpoints and watchpoints.
+/// Front-ends such as `svm --debug` are built on top of this.
+pub struct Debugger {
+    pub machine: Machine,
+
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeMap<u16, u8>,
+}
+
+impl Debugger {
+    #[must_use]
+    pub const fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+        }
+    }
+
+    /// Gives back the machine being debugged.
+    #[must_use]
+    pub fn into_inner(self) -> Machine {
+        self.machine
+    }
+
+    /// Stops execution whenever the program counter reaches `address`.
+    /// Returns false if the breakpoint already existed.
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Returns false if there was no breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stops execution whenever the byte at `address` changes.
+    ///
+    /// # Errors
+    /// Fails if the address can not be read without side effects, such
+    /// as the data port of a console.
+    pub fn add_watchpoint(&mut self, address: u16) -> Result<(), DynErr> {
+        let value = self
+            .machine
+            .memory
+            .peek(address)
+            .ok_or_else(|| format!("Can not watch 0x{address:04X}, it can not be peeked"))?;
+
+        self.watchpoints.insert(address, value);
+        Ok(())
+    }
+
+    /// Returns false if there was no watchpoint at `address`.
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.watchpoints.keys().copied()
+    }
+
+    fn check_watchpoints(&mut self) -> Option<StopReason> {
+        for (&address, old) in &mut self.watchpoints {
+            let Some(new) = self.machine.memory.peek(address) else {
+                continue;
+            };
+
+            if new != *old {
+                let reason = StopReason::Watchpoint {
+                    address,
+                    old: *old,
+                    new,
+                };
+
+                *old = new;
+                return Some(reason);
+            }
+        }
+
+        None
+    }
+
+    fn step_once(&mut self) -> Result<Option<StopReason>, DynErr> {
+        self.machine.step()?;
+
+        if self.machine.machine_halted {
+            return Ok(Some(StopReason::Halted));
+        }
+
+        if let Some(reason) = self.check_watchpoints() {
+            return Ok(Some(reason));
+        }
+
+        let pc = self.machine.get_register(Register::PC);
+        if self.breakpoints.contains(&pc) {
+            return Ok(Some(StopReason::Breakpoint(pc)));
+        }
+
+        Ok(None)
+    }
+
+    /// Executes up to `n` instructions, stopping early on a
+    /// halt, breakpoint or watchpoint.
+    ///
+    /// # Errors
+    /// Fails if the machine fails to step.
+    pub fn step(&mut self, n: usize) -> Result<StopReason, DynErr> {
+        if self.machine.machine_halted {
+            return Ok(StopReason::Halted);
+        }
+
+        for _ in 0..n {
+            if let Some(reason) = self.step_once()? {
+                return Ok(reason);
+            }
+        }
+
+        Ok(StopReason::Stepped)
+    }
+
+    /// Runs until the machine halts or a breakpoint or
+    /// watchpoint is hit.
+    ///
+    /// # Errors
+    /// Fails if the machine fails to step.
+    pub fn run(&mut self) -> Result<StopReason, DynErr> {
+        if self.machine.machine_halted {
+            return Ok(StopReason::Halted);
+        }
+
+        loop {
+            if let Some(reason) = self.step_once()? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Undoes up to `n` steps, returning how many were undone. The
+    /// machine's journal must be enabled with `Machine::enable_journal`.
+    ///
+    /// # Errors
+    /// Fails if memory can not be written back.
+    pub fn step_back(&mut self, n: usize) -> Result<usize, DynErr> {
+        let undone = self.machine.step_back(n)?;
+        self.refresh_watchpoints();
+        Ok(undone)
+    }
+
+    /// Undoes steps until just before the last write to `address`,
+    /// see `Machine::rewind_to_write`.
+    ///
+    /// # Errors
+    /// Fails if memory can not be written back.
+    pub fn rewind_to_write(&mut self, address: u16) -> Result<Option<usize>, DynErr> {
+        let undone = self.machine.rewind_to_write(address)?;
+        self.refresh_watchpoints();
+        Ok(undone)
+    }
+
+    /// Updates the values watchpoints compare against, so that
+    /// going back in time does not trigger them.
+    fn refresh_watchpoints(&mut self) {
+        for (&address, value) in &mut self.watchpoints {
+            if let Some(new) = self.machine.memory.peek(address) {
+                *value = new;
+            }
+        }
+    }
+
+    /// Reads a little endian word without side effects.
+    fn peek_u16(&self, address: u16) -> Result<u16, DynErr> {
+        let byte = |address: u16| {
+            self.machine
+                .memory
+                .peek(address)
+                .ok_or_else(|| format!("0x{address:04X} can not be peeked"))
+        };
+
+        Ok(u16::from_le_bytes([
+            byte(address)?,
+            byte(address.wrapping_add(1))?,
+        ]))
+    }
+
+    /// Decodes the instruction at `address`, returning it with its size.
+    ///
+    /// # Errors
+    /// Fails if memory can not be read without side effects or the
+    /// instruction is invalid.
+    pub fn instruction_at(&self, address: u16) -> Result<(Instruction, u16), DynErr> {
+        let word = self.peek_u16(address)?;
+        let size = Instruction::encoded_size(word);
+
+        let extension = if size > 2 {
+            self.peek_u16(address.wrapping_add(2))?
+        } else {
+            0
+        };
+
+        Ok((Instruction::decode(word, extension)?, size))
+    }
+
+    /// Disassembles `before` words before the program counter up to
+    /// `after` instructions after it. Lines are marked with `>` for the
+    /// program counter and `*` for breakpoints.
+    #[must_use]
+    pub fn disassemble_around_pc(&self, before: u16, after: usize) -> String {
+        let pc = self.machine.get_register(Register::PC);
+        let mut address = pc.saturating_sub(before.saturating_mul(2));
+        let mut remaining = after.saturating_add(1);
+        let mut result = String::new();
+
+        loop {
+            if address >= pc {
+                if remaining == 0 {
+                    break;
+                }
+
+                remaining -= 1;
+            }
+
+            let marker = match (address == pc, self.breakpoints.contains(&address)) {
+                (true, _) => '>',
+                (false, true) => '*',
+                (false, false) => ' ',
+            };
+
+            let (text, size) = match self.instruction_at(address) {
+                Ok((instruction, size)) => (instruction.to_string(), size),
+                Err(_) => ("???".to_string(), 2),
+            };
+
+            let _ = writeln!(result, "{marker} {address:04X} │ {text}");
+
+            match address.checked_add(size) {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+
+        result
+    }
+
+    /// Hex dump of a range of memory, 16 bytes per line. Bytes that
+    /// can not be read without side effects are shown as `--`.
+    #[must_use]
+    pub fn memory_dump(&self, range: RangeInclusive<u16>) -> String {
+        let mut result = String::new();
+        let (start, end) = (*range.start(), *range.end());
+
+        for line in (start..=end).step_by(16) {
+            let _ = write!(result, "{line:04X} │");
+
+            for address in line..=line.saturating_add(15).min(end) {
+                match self.machine.memory.peek(address) {
+                    Some(byte) => {
+                        let _ = write!(result, " {byte:02x}");
+                    }
+                    None => result.push_str(" --"),
+                }
+            }
+
+            result.push('\n');
+        }
+
+        result
+    }
+
+    /// Names of the flags currently set.
+    #[must_use]
+    pub fn flags(&self) -> Vec<String> {
+        Flag::ALL
+            .iter()
+            .filter(|flag| self.machine.test_flag(**flag))
+            .map(|flag| format!("{flag:?}"))
+            .collect()
+    }
+}
@@ -0,0 +1,62 @@
+use crate::op::Instruction;
+
+/// Size in bytes of the widest instruction.
+const MAX_INSTRUCTION_SIZE: u16 = 4;
+
+/// An instruction decoded ahead of time.
+#[derive(Clone, Copy)]
+pub struct Decoded {
+    /// The first word of the instruction, as it was in memory.
+    pub word: u16,
+    pub instruction: Instruction,
+    /// Size of the instruction in bytes.
+    pub size: u16,
+}
+
+/// Decoded instructions by the address they start at, so that running
+/// the same code again skips reading and decoding it.
+pub struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+}
+
+impl DecodeCache {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, address: u16) -> Option<Decoded> {
+        self.entries.get(usize::from(address)).copied().flatten()
+    }
+
+    pub fn insert(&mut self, address: u16, decoded: Decoded) {
+        let index = usize::from(address);
+
+        if index >= self.entries.len() {
+            self.entries.resize(index + 1, None);
+        }
+
+        self.entries[index] = Some(decoded);
+    }
+
+    /// Forgets every instruction that covers the byte at `address`,
+    /// called when the byte is written to.
+    pub fn invalidate(&mut self, address: u16) {
+        let first = address.saturating_sub(MAX_INSTRUCTION_SIZE - 1);
+
+        for start in first..=address {
+            let Some(entry) = self.entries.get_mut(usize::from(start)) else {
+                break;
+            };
+
+            if entry.is_some_and(|d| u32::from(start) + u32::from(d.size) > u32::from(address)) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,133 @@
+use crate::memory;
+use crate::op::{DecodeError, Instruction};
+
+/// The cause of a `VmError`.
+#[derive(Debug)]
+pub enum Fault {
+    /// A value was popped while the stack pointer was below 2.
+    StackUnderflow,
+    /// The low byte of the instruction is not a known opcode.
+    BadOpcode(u8),
+    /// A register operand does not name a register.
+    BadRegister(u8),
+    /// Memory was accessed outside of the mapped addresses.
+    OutOfBounds(u16),
+    /// A `Div` instruction had a divisor of zero.
+    DivideByZero,
+    /// A `Signal` was raised that has no handler.
+    UnknownSignal(u8),
+    /// A device attached to memory refused an access.
+    Device(u16, String),
+    /// The handler for a signal returned an error.
+    SignalFailed(u8, String),
+    /// The trace sink failed to record an instruction.
+    TraceFailed(String),
+    /// An `Int` instruction used a vector with no handler.
+    UnhandledInterrupt(u8),
+}
+
+impl Fault {
+    pub const VECTOR_DIVIDE_BY_ZERO: u8 = 0;
+    pub const VECTOR_INVALID_INSTRUCTION: u8 = 1;
+    pub const VECTOR_STACK_UNDERFLOW: u8 = 2;
+    pub const VECTOR_MEMORY: u8 = 3;
+    pub const VECTOR_UNKNOWN_SIGNAL: u8 = 4;
+
+    /// Interrupt vector that guest code can install a handler on to
+    /// catch this fault, if any.
+    #[must_use]
+    pub const fn vector(&self) -> Option<u8> {
+        match self {
+            Self::DivideByZero => Some(Self::VECTOR_DIVIDE_BY_ZERO),
+            Self::BadOpcode(_) | Self::BadRegister(_) => Some(Self::VECTOR_INVALID_INSTRUCTION),
+            Self::StackUnderflow => Some(Self::VECTOR_STACK_UNDERFLOW),
+            Self::OutOfBounds(_) | Self::Device(..) => Some(Self::VECTOR_MEMORY),
+            Self::UnknownSignal(_) => Some(Self::VECTOR_UNKNOWN_SIGNAL),
+            Self::SignalFailed(..) | Self::TraceFailed(_) | Self::UnhandledInterrupt(_) => None,
+        }
+    }
+
+    /// The memory address the fault refers to, if any.
+    const fn address(&self) -> Option<u16> {
+        match self {
+            Self::OutOfBounds(addr) | Self::Device(addr, _) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Converts an error returned by an `Addressable` access at `addr`.
+    pub(crate) fn from_memory(addr: u16, e: &(dyn std::error::Error + 'static)) -> Self {
+        match e.downcast_ref::<memory::Error>() {
+            Some(memory::Error::OutOfBounds(addr)) => Self::OutOfBounds(*addr),
+            _ => Self::Device(addr, e.to_string()),
+        }
+    }
+}
+
+impl From<DecodeError> for Fault {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::UnknownOpcode(op) | DecodeError::MissingExtension(op) => {
+                Self::BadOpcode(op)
+            }
+            DecodeError::BadRegister(r) => Self::BadRegister(r),
+        }
+    }
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::StackUnderflow => write!(f, "Stack underflow"),
+            Self::BadOpcode(op) => write!(f, "Unknown opcode 0x{op:02X}"),
+            Self::BadRegister(r) => write!(f, "Invalid register {r}"),
+            Self::OutOfBounds(addr) => write!(f, "Out of bounds memory access @ 0x{addr:04X}"),
+            Self::DivideByZero => write!(f, "Division by zero"),
+            Self::UnknownSignal(signal) => write!(f, "Unknown signal 0x{signal:02X}"),
+            Self::Device(addr, message) => write!(f, "Device error @ 0x{addr:04X}: {message}"),
+            Self::SignalFailed(signal, message) => {
+                write!(f, "Signal 0x{signal:02X} failed: {message}")
+            }
+            Self::TraceFailed(message) => write!(f, "Tracing failed: {message}"),
+            Self::UnhandledInterrupt(vector) => {
+                write!(f, "No handler for interrupt vector {vector}")
+            }
+        }
+    }
+}
+
+/// Returned by `Machine::step` when an instruction can not be executed.
+#[derive(Debug)]
+pub struct VmError {
+    /// Address of the faulting instruction.
+    pub pc: u16,
+    /// First word of the faulting instruction, `None` when the fault
+    /// happened before it was fetched.
+    pub instruction: Option<u16>,
+    pub fault: Fault,
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.fault)?;
+
+        // A failed fetch already names the address in the fault.
+        if self.fault.address() != Some(self.pc) {
+            write!(f, " @ 0x{:04X}", self.pc)?;
+        }
+
+        let Some(instruction) = self.instruction else {
+            return Ok(());
+        };
+
+        write!(f, " (instruction 0x{instruction:04X}")?;
+
+        if let Ok(op) = Instruction::try_from(instruction) {
+            write!(f, " `{op}`")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for VmError {}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+
+use crate::vm::REGISTER_COUNT;
+
+/// State of the machine before one step, and the bytes of memory the
+/// step overwrote, so that the step can be undone.
+pub struct Entry {
+    pub registers: [u16; REGISTER_COUNT],
+    pub machine_halted: bool,
+    pub cycles: u64,
+    pub pending_interrupts: u16,
+    /// Addresses written to with the value they held before, in the
+    /// order they were written.
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl Entry {
+    pub fn wrote(&self, address: u16) -> bool {
+        self.writes.iter().any(|(a, _)| *a == address)
+    }
+}
+
+/// Undo history of the most recent steps, oldest first.
+pub struct Journal {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl Journal {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Starts recording a step, forgetting the oldest one when full.
+    pub fn begin(&mut self, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    /// Records the old value of a byte written by the current step.
+    pub fn record_write(&mut self, address: u16, old: u8) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.writes.push((address, old));
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// How many steps back the most recent write to `address` is.
+    pub fn steps_since_write(&self, address: u16) -> Option<usize> {
+        self.entries
+            .iter()
+            .rev()
+            .position(|entry| entry.wrote(address))
+            .map(|steps| steps + 1)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
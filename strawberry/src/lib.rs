@@ -35,7 +35,7 @@
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let mut vm = Machine::new();
 //!
-//!     vm.define_handler(0xF0, |machine| machine.machine_halted = true);
+//!     vm.define_handler(0xF0, |_| Ok(SignalAction::Halt));
 //!
 //!     write_memory!(vm,
 //!        // Push 10; Pop A; Push 8; Pop B; Add A B; Signal 0xF0 (halt)
@@ -63,11 +63,22 @@
 //! }
 //! ```
 
+mod address;
+pub mod bus;
+pub mod coverage;
+pub mod debugger;
+mod decode_cache;
+mod error;
+mod journal;
 mod macros;
-mod memory;
+pub mod memory;
 mod op;
 pub mod panic_report;
+pub mod profile;
 mod register;
+pub mod snapshot;
+pub mod trace;
+pub mod video;
 mod vm;
 
 /// Can be included to get everything useful
@@ -75,6 +86,10 @@ mod vm;
 pub mod prelude {
     pub use crate::write_memory;
 
+    pub use crate::memory::{Addressable, Linear};
+
+    pub use crate::address::*;
+    pub use crate::error::*;
     pub use crate::op::*;
     pub use crate::register::*;
     pub use crate::vm::*;
@@ -85,13 +100,30 @@ mod tests {
     #![allow(clippy::unwrap_used)]
 
     use crate::{
+        op::Instruction,
         register::Register,
-        vm::{Machine, MEMORY_KILO_BYTES},
+        vm::{
+            Machine, RunOutcome, SignalAction, SignalResult, MAX_MEMORY_BYTES, MEMORY_KILO_BYTES,
+        },
         write_memory,
     };
 
-    fn sig_halt(vm: &mut Machine) {
-        vm.machine_halted = true;
+    #[allow(clippy::unnecessary_wraps)]
+    fn sig_halt(_: &mut Machine) -> SignalResult {
+        Ok(SignalAction::Halt)
+    }
+
+    /// Writes `words` to memory one after another, starting at `start`.
+    fn load_words(
+        machine: &mut Machine,
+        start: u16,
+        words: &[u16],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (addr, word) in (start..).step_by(2).zip(words) {
+            machine.memory.write_u16(addr, *word)?;
+        }
+
+        Ok(())
     }
 
     // Tests for failure (these should fail!)
@@ -103,6 +135,781 @@ mod tests {
         assert!(machine.step().is_err());
     }
 
+    #[test]
+    fn structured_faults() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Fault;
+
+        let fault = |words: &[u16]| -> Result<(u16, Fault), Box<dyn std::error::Error>> {
+            let mut machine = Machine::builder().stack_pointer(0x100).build()?;
+
+            load_words(&mut machine, 0, words)?;
+
+            loop {
+                if let Err(e) = machine.step() {
+                    assert_eq!(Some(machine.memory.read_u16(e.pc)?), e.instruction);
+                    return Ok((e.pc, e.fault));
+                }
+            }
+        };
+
+        // LoadImm A 5; Div A B
+        assert!(matches!(fault(&[0x0014, 5, 0x1029])?, (4, Fault::DivideByZero)));
+        // Mov A <register 15>
+        assert!(matches!(fault(&[0xF013])?, (0, Fault::BadRegister(15))));
+        // Signal 0x42
+        assert!(matches!(fault(&[0x4250])?, (0, Fault::UnknownSignal(0x42))));
+        // LoadImm B $FFFF; Load A B
+        assert!(matches!(
+            fault(&[0x0114, 0xFFFF, 0x1040])?,
+            (4, Fault::OutOfBounds(0xFFFF))
+        ));
+
+        let mut machine = Machine::new();
+        machine.memory.write(0, 0x11)?; // Pop A
+        let e = machine.step().unwrap_err();
+        assert!(matches!(e.fault, Fault::StackUnderflow));
+        assert_eq!(
+            e.to_string(),
+            "Stack underflow @ 0x0000 (instruction 0x0011 `Pop A`)"
+        );
+
+        // Jumping off the end of memory fails to fetch the next instruction
+        let mut machine = Machine::builder().memory_size(0x400).build()?;
+        machine.set_register(Register::PC, 0x400);
+        let e = machine.step().unwrap_err();
+        assert_eq!(e.instruction, None);
+        assert_eq!(e.to_string(), "Out of bounds memory access @ 0x0400");
+
+        Ok(())
+    }
+
+    #[test]
+    fn interrupts() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Fault;
+        use crate::register::Flag;
+
+        let mut machine = Machine::builder().stack_pointer(0x100).build()?;
+        machine.define_handler(0xf0, sig_halt);
+
+        let table = machine.interrupt_table;
+        assert_eq!(table, 0x3E0);
+
+        // Divide by zero and vector 8 both go to the handler at 0x40.
+        machine.memory.write_u16(table, 0x40)?;
+        machine.memory.write_u16(table + 16, 0x40)?;
+
+        let program: &[u16] = &[
+            0x0014, 5, // LoadImm A 5
+            0x1029, // Div A B
+            0x0863, // Int 8
+            0x0065, // Ei
+            0x0000, // Nop
+            0xf050, // Signal 0xF0
+        ];
+
+        // Push 1; Pop D; Add C D; Iret
+        let handler: &[u16] = &[0x0110, 0x0311, 0x3220, 0x0064];
+
+        load_words(&mut machine, 0, program)?;
+
+        load_words(&mut machine, 0x40, handler)?;
+
+        machine.step()?; // LoadImm A 5
+        machine.step()?; // Div A B, traps
+        assert_eq!(machine.get_register(Register::PC), 0x40);
+        for _ in handler {
+            machine.step()?;
+        }
+        assert_eq!(machine.get_register(Register::PC), 6);
+        assert_eq!(machine.get_register(Register::C), 1);
+
+        // Masked until interrupts are enabled.
+        assert!(machine.raise_interrupt(8));
+        assert!(!machine.raise_interrupt(16));
+
+        machine.step()?; // Int 8
+        assert_eq!(machine.get_register(Register::PC), 0x40);
+        assert!(!machine.test_flag(Flag::InterruptEnable));
+        for _ in handler {
+            machine.step()?;
+        }
+        assert_eq!(machine.get_register(Register::C), 2);
+
+        machine.step()?; // Ei
+        machine.step()?; // Services the pending interrupt
+        assert_eq!(machine.get_register(Register::PC), 0x40);
+        for _ in handler {
+            machine.step()?;
+        }
+        assert!(machine.test_flag(Flag::InterruptEnable));
+        assert_eq!(machine.get_register(Register::C), 3);
+        assert_eq!(machine.get_register(Register::SP), 0x100);
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        let mut machine = Machine::builder().stack_pointer(0x100).build()?;
+        machine.memory.write_u16(0, 0x0963)?; // Int 9
+        let e = machine.step().unwrap_err();
+        assert!(matches!(e.fault, Fault::UnhandledInterrupt(9)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn stateful_signals() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Fault;
+        use std::{cell::RefCell, rc::Rc};
+
+        let mut machine = Machine::new();
+        let printed = Rc::new(RefCell::new(Vec::new()));
+
+        let recorder = Rc::clone(&printed);
+        machine.define_handler(0xF1, move |vm| {
+            recorder.borrow_mut().push(vm.signal_args().a);
+            Ok(SignalAction::Continue)
+        });
+        machine.define_handler(0xF0, sig_halt);
+
+        // LoadImm A 7; Signal 0xF1; LoadImm A 9; Signal 0xF1; Signal 0xF0
+        let program: &[u16] = &[0x0014, 7, 0xF150, 0x0014, 9, 0xF150, 0xF050];
+        load_words(&mut machine, 0, program)?;
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(*printed.borrow(), [7, 9]);
+
+        // A handler that removes itself runs once
+        let mut machine = Machine::new();
+        let calls = Rc::new(RefCell::new(0));
+
+        let counter = Rc::clone(&calls);
+        machine.define_handler(0xF1, move |vm| {
+            *counter.borrow_mut() += 1;
+            assert!(vm.remove_handler(0xF1).is_none());
+            Ok(SignalAction::Continue)
+        });
+
+        // Signal 0xF1; Signal 0xF1
+        load_words(&mut machine, 0, &[0xF150, 0xF150])?;
+        machine.step()?;
+        let e = machine.step().unwrap_err();
+        assert!(matches!(e.fault, Fault::UnknownSignal(0xF1)));
+        assert_eq!(*calls.borrow(), 1);
+
+        let mut machine = Machine::new();
+        machine.define_handler(0xF2, |_| Err("no output".into()));
+        machine.memory.write_u16(0, 0xF250)?; // Signal 0xF2
+        let e = machine.step().unwrap_err();
+        assert!(matches!(e.fault, Fault::SignalFailed(0xF2, _)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cycle_budget() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::new();
+        machine.memory.write_u16(0, 0xFF31)?; // Jmp -1
+
+        assert_eq!(machine.run_for(10)?, RunOutcome::BudgetExhausted);
+        assert_eq!(machine.cycles(), 10);
+        assert_eq!(machine.run_for(1)?, RunOutcome::BudgetExhausted);
+        assert_eq!(machine.cycles(), 12);
+
+        let mut machine = Machine::new();
+        machine.define_handler(0xf0, sig_halt);
+
+        // LoadImm A 5; Div A A; Signal 0xF0
+        let program: &[u16] = &[0x0014, 5, 0x0029, 0xF050];
+        load_words(&mut machine, 0, program)?;
+
+        assert_eq!(machine.run_for(u64::MAX)?, RunOutcome::Halted);
+        assert_eq!(machine.cycles(), 2 + 8 + 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn framebuffer() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::bus::Bus;
+        use crate::memory::Linear;
+        use crate::video::{Framebuffer, WIDTH};
+
+        let framebuffer = Framebuffer::new();
+        let screen = framebuffer.screen();
+
+        let mut bus = Bus::new();
+        bus.attach(0x0000, Linear::new(0x100))?;
+        bus.attach(0x8000, framebuffer)?;
+
+        let mut machine = Machine::builder()
+            .memory(Box::new(bus))
+            .stack_pointer(0x80)
+            .build()?;
+        machine.define_handler(0xf0, sig_halt);
+
+        // LoadImm A 8; LoadImm B $8041; Store A B; LoadImm B $8C00; Store A B; Signal 0xF0
+        let program: &[u16] = &[
+            0x0014, 8, 0x0114, 0x8041, 0x1041, 0x0114, 0x8C00, 0x1041, 0xF050,
+        ];
+        load_words(&mut machine, 0, program)?;
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        // Storing to the control port at 0x8C00 presents the frame.
+        assert_eq!(screen.frames_presented(), 1);
+
+        let frame = screen.frame();
+        assert_eq!(frame.pixel(0x41 % WIDTH, 0x41 / WIDTH), Some(8));
+        // The high byte of the word store lands in the next pixel.
+        assert_eq!(frame.pixel(0x42 % WIDTH, 0x42 / WIDTH), Some(0));
+
+        let mut ppm = Vec::new();
+        frame.write_ppm(&mut ppm)?;
+        assert!(ppm.starts_with(b"P6\n64 48\n255\n"));
+        assert_eq!(ppm.len(), 13 + 64 * 48 * 3);
+
+        let mut png = Vec::new();
+        frame.write_png(&mut png)?;
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // The IEND chunk and its well known CRC.
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn console_signals() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::bus::Console;
+
+        let mut machine = Machine::new();
+        machine.define_handler(0xf0, sig_halt);
+
+        let (console, output) = Console::buffered("x");
+        console.install_signals(&mut machine);
+
+        let program: &[u16] = &[
+            0xE150, // Signal 0xE1 (getchar)
+            0xE050, // Signal 0xE0 (putchar)
+            0xE150, // Signal 0xE1, A = $FFFF at the end of input
+            0x0114, 16,     // LoadImm B 16
+            0xE250, // Signal 0xE2 (print number)
+            0x0014, 0x100,  // LoadImm A $100
+            0xE350, // Signal 0xE3 (print string)
+            0xF050, // Signal 0xF0
+        ];
+        load_words(&mut machine, 0, program)?;
+
+        for (addr, byte) in (0x100..).zip(b" ok\0") {
+            machine.memory.write(addr, *byte)?;
+        }
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(output.contents(), b"xffff ok");
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshots() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::snapshot::{Snapshot, SnapshotError};
+
+        let mut machine = Machine::new();
+        machine.define_handler(0xf0, sig_halt);
+
+        // LoadImm A 5; LoadImm B 7; Add A B; Signal 0xF0
+        let program: &[u16] = &[0x0014, 5, 0x0114, 7, 0x1020, 0xF050];
+        load_words(&mut machine, 0, program)?;
+
+        machine.step()?;
+        machine.step()?;
+
+        let mut saved = Vec::new();
+        machine.snapshot()?.write_to(&mut saved)?;
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+        assert_eq!(machine.get_register(Register::A), 12);
+
+        let snapshot = Snapshot::read_from(saved.as_slice())?;
+        machine.restore(&snapshot)?;
+        assert!(!machine.machine_halted);
+        assert_eq!(machine.get_register(Register::A), 5);
+        assert_eq!(machine.get_register(Register::PC), 8);
+        assert_eq!(machine.cycles(), 4);
+
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::A), 12);
+        assert_eq!(machine.snapshot()?.memory, snapshot.memory);
+
+        assert!(matches!(
+            Snapshot::read_from(&saved[..saved.len() - 1]),
+            Err(SnapshotError::Truncated)
+        ));
+        assert!(matches!(
+            Snapshot::read_from(&b"nope"[..]),
+            Err(SnapshotError::BadMagic)
+        ));
+
+        // Memory of a different size is rejected.
+        let mut small = Machine::builder().memory_size(512).build()?;
+        assert!(small.restore(&snapshot).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_execution() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::builder().stack_pointer(0x200).build()?;
+        machine.define_handler(0xf0, sig_halt);
+        machine.enable_journal(16);
+
+        let program: &[u16] = &[
+            0x0014, 0x1234, // LoadImm A $1234
+            0x0114, 0x100,  // LoadImm B $100
+            0x1041, // Store A B
+            0x0012, // PushReg A
+            0x0027, // Not A
+            0x1041, // Store A B
+            0xF050, // Signal 0xF0
+        ];
+        load_words(&mut machine, 0, program)?;
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.journal_len(), 7);
+        assert_eq!(machine.memory.read_u16(0x100)?, !0x1234);
+
+        // Back to just before the second store.
+        assert_eq!(machine.rewind_to_write(0x100)?, Some(2));
+        assert!(!machine.machine_halted);
+        assert_eq!(machine.get_register(Register::PC), 14);
+        assert_eq!(machine.get_register(Register::A), !0x1234);
+        assert_eq!(machine.memory.read_u16(0x100)?, 0x1234);
+
+        // Undo Not A and PushReg A, including the pushed bytes.
+        assert_eq!(machine.step_back(2)?, 2);
+        assert_eq!(machine.get_register(Register::A), 0x1234);
+        assert_eq!(machine.get_register(Register::SP), 0x200);
+        assert_eq!(machine.memory.read_u16(0x200)?, 0);
+
+        assert_eq!(machine.rewind_to_write(0x100)?, Some(1));
+        assert_eq!(machine.memory.read_u16(0x100)?, 0);
+        assert_eq!(machine.rewind_to_write(0x100)?, None);
+
+        assert_eq!(machine.step_back(10)?, 2);
+        assert_eq!(machine.get_register(Register::PC), 0);
+        assert_eq!(machine.cycles(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tracing() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::register::Flag;
+        use crate::trace::{JsonLines, TraceRecord, TraceSink};
+        use std::{cell::RefCell, rc::Rc};
+
+        struct Recorder(Rc<RefCell<Vec<TraceRecord>>>);
+
+        impl TraceSink for Recorder {
+            fn record(&mut self, record: &TraceRecord) -> Result<(), Box<dyn std::error::Error>> {
+                self.0.borrow_mut().push(record.clone());
+                Ok(())
+            }
+        }
+
+        let records = Rc::new(RefCell::new(Vec::new()));
+
+        let mut machine = Machine::new();
+        machine.set_trace_sink(Box::new(Recorder(Rc::clone(&records))));
+
+        // LoadImm B $100; Store B B; Load A B; Cmp A B; Div A C
+        let program: &[u16] = &[0x0114, 0x100, 0x1141, 0x1040, 0x1030, 0x2029];
+        load_words(&mut machine, 0, program)?;
+
+        for _ in 0..4 {
+            machine.step()?;
+        }
+        assert!(machine.step().is_err());
+
+        let records = records.borrow();
+        assert_eq!(records.len(), 5);
+
+        assert_eq!(records[0].registers, [(Register::B, 0, 0x100)]);
+        assert_eq!(records[1].writes, [(0x100, 0x100)]);
+        assert_eq!(records[2].reads, [(0x100, 0x100)]);
+        assert_eq!(records[2].registers, [(Register::A, 0, 0x100)]);
+        assert_eq!(records[3].pc, 8);
+        assert_eq!(records[3].flags, [(Flag::Compare, true)]);
+        assert_eq!(
+            records[4].instruction,
+            Instruction::Div(Register::A, Register::C)
+        );
+        assert_eq!(records[4].fault.as_deref(), Some("Division by zero"));
+
+        let mut sink = JsonLines::new(Vec::new());
+        sink.record(&records[3])?;
+        assert_eq!(
+            String::from_utf8(sink.into_inner())?,
+            "{\"pc\":8,\"op\":\"Cmp A B\",\"regs\":{},\"flags\":{\"Compare\":true},\"reads\":[],\"writes\":[]}\n"
+        );
+
+        // Byte accesses are kept apart from word accesses
+        let records = Rc::new(RefCell::new(Vec::new()));
+
+        let mut machine = Machine::new();
+        machine.set_trace_sink(Box::new(Recorder(Rc::clone(&records))));
+
+        // LoadImm B $100; LoadImm A $1234; StoreB A B; LoadB C B
+        let program: &[u16] = &[0x0114, 0x100, 0x0014, 0x1234, 0x1044, 0x1242];
+        load_words(&mut machine, 0, program)?;
+
+        for _ in 0..4 {
+            machine.step()?;
+        }
+
+        let records = records.borrow();
+        assert_eq!(records[2].byte_writes, [(0x100, 0x34)]);
+        assert!(records[2].writes.is_empty());
+        assert_eq!(records[3].byte_reads, [(0x100, 0x34)]);
+        assert!(records[3].reads.is_empty());
+        assert_eq!(
+            records[3].to_json(),
+            "{\"pc\":10,\"op\":\"LoadB C B\",\"regs\":{\"C\":[0,52]},\"flags\":{},\"reads\":[],\"writes\":[],\"byte_reads\":[[256,52]]}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn profiling() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::profile::Profiler;
+
+        let profiler = Profiler::new();
+
+        let mut machine = Machine::builder().stack_pointer(0x200).build()?;
+        machine.define_handler(0xf0, sig_halt);
+        machine.set_trace_sink(Box::new(profiler.clone()));
+
+        // Call +1; Signal 0xF0; Nop; Ret
+        let program: &[u16] = &[0x0160, 0xF050, 0x0000, 0x0062];
+        load_words(&mut machine, 0, program)?;
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        let profile = profiler.profile();
+        assert_eq!(profile.total().count, 4);
+        assert_eq!(profile.total().cycles, machine.cycles());
+
+        let (address, stats) = profile.hottest(1)[0];
+        assert_eq!(address, 2);
+        assert_eq!(stats.instruction, Instruction::Signal(0xf0));
+        assert_eq!(profile.opcodes()[0].0, "Signal");
+
+        assert_eq!(
+            profile.folded(|address| format!("{address}")),
+            "0 9\n0;4 5\n"
+        );
+
+        // A trapped fault enters a frame of its own that `Iret` leaves
+        let profiler = Profiler::new();
+
+        let mut machine = Machine::builder().stack_pointer(0x200).build()?;
+        machine.define_handler(0xf0, sig_halt);
+        machine.set_trace_sink(Box::new(profiler.clone()));
+        machine.memory.write_u16(machine.interrupt_table, 0x40)?;
+
+        // Call +1; Signal 0xF0; Div A B; Ret
+        let program: &[u16] = &[0x0160, 0xF050, 0x1029, 0x0062];
+        load_words(&mut machine, 0, program)?;
+        machine.memory.write_u16(0x40, 0x0064)?; // Iret
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(
+            profiler.profile().folded(|address| format!("{address}")),
+            "0 9\n0;4 12\n0;4;64 5\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn coverage() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::coverage::{Branch, CoverageRecorder};
+
+        let recorder = CoverageRecorder::new();
+
+        let mut machine = Machine::new();
+        machine.set_trace_sink(Box::new(recorder.clone()));
+
+        // Cmp A B; Je +1; Nop; Jne +5; Nop
+        let program: &[u16] = &[0x1030, 0x0132, 0x0000, 0x0533, 0x0000];
+        load_words(&mut machine, 0, program)?;
+
+        for _ in 0..4 {
+            machine.step()?;
+        }
+
+        let coverage = recorder.coverage();
+        assert_eq!(
+            coverage.executed().collect::<Vec<_>>(),
+            [(0, 1), (2, 1), (6, 1), (8, 1)]
+        );
+        assert_eq!(coverage.hits(4), 0);
+
+        let taken = Branch { taken: 1, not_taken: 0 };
+        let not_taken = Branch { taken: 0, not_taken: 1 };
+        assert_eq!(coverage.branch(2), Some(taken));
+        assert_eq!(coverage.branch(6), Some(not_taken));
+        assert_eq!(coverage.branch(0), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::new();
+
+        // LoadImm A $0127; LoadImm B 12; Store A B; Nop; Nop
+        let program: &[u16] = &[0x0014, 0x0127, 0x0114, 12, 0x1041, 0x0000, 0x0000];
+        load_words(&mut machine, 0, program)?;
+
+        assert_eq!(machine.predecode(0, program.len() * 2), 5);
+
+        // The store overwrites the cached `Nop` at 12 with `Not B`.
+        for _ in 0..5 {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.get_register(Register::B), !12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn panic_report() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::panic_report::get_last_status;
+
+        let mut machine = Machine::new();
+
+        // Nop; LoadImm A $1234
+        let program: &[u16] = &[0x0000, 0x0014, 0x1234];
+        load_words(&mut machine, 0, program)?;
+
+        machine.step()?;
+        machine.step()?;
+
+        let status = get_last_status().ok_or("No status was recorded")?;
+        assert!(status.starts_with("PC: 0x0002\nINSTR: 0x0014\n"));
+
+        // Registers are recorded before the instruction runs.
+        assert_eq!(machine.get_register(Register::A), 4660);
+        assert!(!status.contains("4660"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let jumps: [(&str, u16); 9] = [
+            ("Jl", 0x34),
+            ("Jg", 0x35),
+            ("Jle", 0x36),
+            ("Jge", 0x37),
+            ("Jb", 0x38),
+            ("Ja", 0x39),
+            ("Jc", 0x3A),
+            ("Jo", 0x3B),
+            ("Jn", 0x3C),
+        ];
+
+        // Operands of `Cmp A B`, and the jumps taken after it.
+        let cases: [(u16, u16, &[&str]); 5] = [
+            (1, 2, &["Jl", "Jle", "Jb", "Jc", "Jn"]),
+            (2, 1, &["Jg", "Jge", "Ja"]),
+            (2, 2, &["Jle", "Jge"]),
+            (0xFFFF, 1, &["Jl", "Jle", "Ja", "Jn"]),
+            (0x7FFF, 0xFFFF, &["Jg", "Jge", "Jb", "Jc", "Jo", "Jn"]),
+        ];
+
+        for (a, b, taken) in cases {
+            for (name, opcode) in jumps {
+                let mut machine = Machine::new();
+                machine.set_register(Register::A, a);
+                machine.set_register(Register::B, b);
+
+                // Cmp A B; J.. +1
+                machine.memory.write_u16(0, 0x1030)?;
+                machine.memory.write_u16(2, 0x0100 | opcode)?;
+
+                machine.step()?;
+                machine.step()?;
+
+                let jumped = machine.get_register(Register::PC) == 6;
+                assert_eq!(jumped, taken.contains(&name), "Cmp {a:#x} {b:#x}; {name}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn carry() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::register::Flag;
+
+        let mut machine = Machine::new();
+        machine.set_register(Register::A, 0xFFFF);
+        machine.set_register(Register::B, 1);
+        machine.set_register(Register::C, 0x8001);
+
+        // Add A B; Shr B B; Shl C B
+        let program: &[u16] = &[0x1020, 0x1123, 0x1222];
+        load_words(&mut machine, 0, program)?;
+
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::A), 0);
+        assert!(machine.test_flag(Flag::Carry));
+        assert!(!machine.test_flag(Flag::Overflow));
+
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::B), 0);
+        assert!(machine.test_flag(Flag::Carry));
+
+        // A shift by 0 leaves Carry alone.
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::C), 0x8001);
+        assert!(machine.test_flag(Flag::Carry));
+
+        Ok(())
+    }
+
+    #[test]
+    fn immediate_forms() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Fault;
+        use crate::op::Imm4;
+        use crate::register::Flag;
+
+        let nine = Imm4::new(9).unwrap();
+        assert_eq!(Instruction::AddI(Register::A, nine).encode(), vec![0x9070]);
+        assert_eq!(
+            Instruction::try_from(0x9070)?,
+            Instruction::AddI(Register::A, nine)
+        );
+        assert!(Imm4::new(16).is_none());
+
+        let mut machine = Machine::new();
+        machine.set_register(Register::A, 7);
+
+        // AddI A 9; ShlI A 3; CmpI A 15; SubI A 1; DivI A 0
+        let program: &[u16] = &[0x9070, 0x3072, 0xF03D, 0x1071, 0x0079];
+        load_words(&mut machine, 0, program)?;
+
+        machine.step()?;
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::A), 128);
+
+        machine.step()?;
+        assert!(!machine.test_flag(Flag::Compare));
+        assert!(!machine.test_flag(Flag::Carry));
+
+        machine.step()?;
+        assert_eq!(machine.get_register(Register::A), 127);
+
+        assert!(matches!(
+            machine.step().unwrap_err().fault,
+            Fault::DivideByZero
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn byte_access() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::new();
+        machine.memory.write_u16(0x100, 0xF234)?;
+
+        // LoadImm B $100; LoadB A B; LoadImm C $101; LoadBS D C; StoreB D B
+        let program: &[u16] = &[0x0114, 0x100, 0x1042, 0x0214, 0x101, 0x2343, 0x1344];
+        load_words(&mut machine, 0, program)?;
+
+        for _ in 0..5 {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.get_register(Register::A), 0x34);
+        assert_eq!(machine.get_register(Register::D), 0xFFF2);
+        assert_eq!(machine.memory.read_u16(0x100)?, 0xF2F2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn addressing_modes() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::address::{Indexed, Offset};
+        use std::str::FromStr;
+
+        let local = Offset {
+            base: Register::BP,
+            offset: 0xFFFE,
+        };
+        let element = Indexed {
+            base: Register::B,
+            index: Register::C,
+        };
+
+        let program = [
+            Instruction::StoreOff(Register::A, local),
+            Instruction::LoadOff(Register::D, local),
+            Instruction::StoreBIdx(Register::D, element),
+            Instruction::LoadBSIdx(Register::C, element),
+        ];
+
+        assert_eq!(program[0].encode(), vec![0x6047, 0xFFFE]);
+        assert_eq!(program[2].encode(), vec![0x134E, 0x0002]);
+        assert_eq!(program[1].to_string(), "LoadOff D [BP-2]");
+        assert_eq!(program[3].to_string(), "LoadBSIdx C [B+C]");
+        assert_eq!(Instruction::from_str("LoadOff D [BP-2]")?, program[1]);
+        assert_eq!(Instruction::from_str("StoreOff A [BP+$FFFE]")?, program[0]);
+        assert!(Instruction::from_str("LoadIdx A [B+4]").is_err());
+
+        let mut machine = Machine::new();
+        machine.set_register(Register::A, 0xBE80);
+        machine.set_register(Register::BP, 0x200);
+        machine.set_register(Register::B, 0x100);
+        machine.set_register(Register::C, 3);
+
+        let words: Vec<u16> = program.iter().flat_map(Instruction::encode).collect();
+        load_words(&mut machine, 0, &words)?;
+
+        for _ in 0..program.len() {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.memory.read_u16(0x1FE)?, 0xBE80);
+        assert_eq!(machine.get_register(Register::D), 0xBE80);
+        assert_eq!(machine.memory.read(0x103)?, 0x80);
+        assert_eq!(machine.get_register(Register::C), 0xFF80);
+
+        Ok(())
+    }
+
     #[test]
     fn out_of_bounds() -> Result<(), Box<dyn std::error::Error>> {
         let mut machine = Machine::new();
@@ -115,6 +922,184 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn full_address_space() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::builder()
+            .memory_size(MAX_MEMORY_BYTES)
+            .stack_pointer(0xF000)
+            .program_counter(0x0100)
+            .build()?;
+
+        assert_eq!(machine.get_register(Register::SP), 0xF000);
+        assert_eq!(machine.get_register(Register::PC), 0x0100);
+
+        machine.memory.write(0xFFFF, 0xA)?;
+        assert_eq!(machine.memory.read(0xFFFF)?, 0xA);
+
+        // A word starting at the last byte would wrap around the address space.
+        assert!(machine.memory.read_u16(0xFFFF).is_err());
+        assert!(machine.memory.write_u16(0xFFFF, 0).is_err());
+
+        assert!(Machine::builder()
+            .memory_size(MAX_MEMORY_BYTES + 1)
+            .build()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bus_routes_to_devices() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::bus::{Bus, Console, Rom, Timer};
+        use crate::memory::Linear;
+
+        let mut bus = Bus::new();
+        bus.attach(0x0000, Linear::new(0x100))?;
+        bus.attach(0x8000, Rom::new(&[0x12, 0x34]))?;
+        bus.attach(0xFF00, Timer::new())?;
+        bus.attach(
+            0xFF10,
+            Console::new(Box::new(&b"hi"[..]), Box::new(std::io::sink())),
+        )?;
+
+        assert!(bus.attach(0x00FF, Timer::new()).is_err());
+
+        let mut machine = Machine::with_memory(Box::new(bus));
+        machine.define_handler(0xf0, sig_halt);
+
+        assert_eq!(machine.memory.read_u16(0x8000)?, 0x3412);
+        assert!(machine.memory.write(0x8000, 0).is_err());
+        assert!(machine.memory.read(0x4000).is_err());
+
+        // The debugger must not consume console input
+        let mut debugger = crate::debugger::Debugger::new(machine);
+        assert!(debugger.add_watchpoint(0xFF10).is_err());
+        assert!(debugger.instruction_at(0xFF10).is_err());
+        assert_eq!(debugger.memory_dump(0xFF10..=0xFF11), "FF10 │ -- --\n");
+        let mut machine = debugger.into_inner();
+        assert_eq!(machine.predecode(0xFF10, 2), 0);
+
+        assert_eq!(machine.memory.read(0xFF11)?, 1);
+        assert_eq!(machine.memory.read(0xFF10)?, b'h');
+        assert_eq!(machine.memory.read(0xFF10)?, b'i');
+        assert_eq!(machine.memory.read(0xFF11)?, 0);
+
+        write_memory!(machine,
+         // Nop; Nop; Signal 0xF0
+         0 => 0x00,
+         2 => 0x00,
+         4 => 0x50,
+         5 => 0xf0
+        );
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.memory.read_u16(0xFF00)?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_and_return() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::builder().stack_pointer(0x200).build()?;
+        machine.define_handler(0xf0, sig_halt);
+
+        write_memory!(machine,
+         // Call +2; Signal 0xF0; Nop; Push 7; Pop A; Ret
+         0 => 0x60,
+         1 => 0x02,
+         2 => 0x50,
+         3 => 0xf0,
+         6 => 0x10,
+         7 => 0x07,
+         8 => 0x11,
+         9 => 0x00,
+         10 => 0x62
+        );
+
+        machine.step()?; // Call +2
+        assert_eq!(machine.get_register(Register::PC), 6);
+        assert_eq!(machine.memory.read_u16(0x200)?, 2);
+
+        while !machine.machine_halted {
+            machine.step()?;
+        }
+
+        assert_eq!(machine.get_register(Register::A), 7);
+        assert_eq!(machine.get_register(Register::SP), 0x200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wide_immediate() -> Result<(), Box<dyn std::error::Error>> {
+        let mut machine = Machine::new();
+
+        let instruction = Instruction::LoadImm(Register::C, 0xBEEF);
+        assert_eq!(instruction.encode(), vec![0x0214, 0xBEEF]);
+
+        load_words(&mut machine, 0, &instruction.encode())?;
+
+        machine.step()?;
+
+        assert_eq!(machine.get_register(Register::C), 0xBEEF);
+        assert_eq!(machine.get_register(Register::PC), 4);
+
+        let mut machine = Machine::builder().stack_pointer(0x100).build()?;
+
+        let instruction = Instruction::PushWide(0x1234);
+        assert_eq!(instruction.encode(), vec![0x0015, 0x1234]);
+
+        load_words(&mut machine, 0, &instruction.encode())?;
+
+        machine.step()?;
+
+        assert_eq!(machine.memory.read_u16(0x100)?, 0x1234);
+        assert_eq!(machine.get_register(Register::SP), 0x102);
+        assert_eq!(machine.get_register(Register::PC), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn debugger_stops() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::debugger::{Debugger, StopReason};
+
+        let mut machine = Machine::builder().stack_pointer(0x100).build()?;
+        machine.define_handler(0xf0, sig_halt);
+
+        write_memory!(machine,
+         // Push 10; Nop; Nop; Signal 0xF0
+         0 => 0x10,
+         1 => 0x0a,
+         6 => 0x50,
+         7 => 0xf0
+        );
+
+        let mut debugger = Debugger::new(machine);
+        debugger.add_breakpoint(4);
+        debugger.add_watchpoint(0x100)?;
+
+        let listing = debugger.disassemble_around_pc(u16::MAX, usize::MAX);
+        assert!(listing.starts_with("> 0000 │ Push 10\n  0002 │ Nop\n* 0004 │ Nop\n"));
+
+        assert_eq!(
+            debugger.run()?,
+            StopReason::Watchpoint {
+                address: 0x100,
+                old: 0,
+                new: 10
+            }
+        );
+        assert_eq!(debugger.run()?, StopReason::Breakpoint(4));
+        assert_eq!(debugger.step(1)?, StopReason::Stepped);
+        assert_eq!(debugger.run()?, StopReason::Halted);
+
+        Ok(())
+    }
+
     #[test]
     fn addition() -> Result<(), Box<dyn std::error::Error>> {
         let mut machine = Machine::new();
@@ -9,48 +9,114 @@ pub trait Addressable {
 
     /// Read function implemented uniquely for reading
     /// a single byte.
+    ///
+    /// # Errors
+    /// Fails if the address is outside of the memory.
     fn read(&self, addr: u16) -> Result<u8, DynErr>;
 
     /// Read function implemented uniquely for writing
     /// a single byte.
+    ///
+    /// # Errors
+    /// Fails if the address is outside of the memory.
     fn write(&mut self, addr: u16, value: u8) -> Result<(), DynErr>;
 
+    /// Called once for every instruction the machine executes,
+    /// used by memory that has devices attached.
+    fn tick(&mut self) {}
+
+    /// Reads a byte without side effects, for tools that inspect memory
+    /// such as the journal. Returns `None` if the byte can't be read
+    /// that way.
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.read(addr).ok()
+    }
+
+    /// Every byte of the memory, used to save snapshots.
+    ///
+    /// # Errors
+    /// Fails by default, as memory with devices attached can not
+    /// always be read without side effects.
+    fn contents(&self) -> Result<Vec<u8>, DynErr> {
+        Err("This memory can not be saved".into())
+    }
+
+    /// Replaces every byte of the memory, used to restore snapshots.
+    ///
+    /// # Errors
+    /// Fails if `bytes` is not the size of the memory, and by default.
+    fn restore(&mut self, _bytes: &[u8]) -> Result<(), DynErr> {
+        Err("This memory can not be restored".into())
+    }
+
+    /// Reads a little endian u16 from two consecutive bytes.
+    ///
+    /// # Errors
+    /// Fails if either byte is outside of the memory.
     fn read_u16(&self, addr: u16) -> Result<u16, DynErr> {
         if let Ok(x0) = self.read(addr) {
-            if let Ok(x1) = self.read(addr + 1) {
+            if let Some(Ok(x1)) = addr.checked_add(1).map(|next| self.read(next)) {
                 return Ok(u16::from(x0) | (u16::from(x1) << 8));
             }
-        };
+        }
 
         Err(Error::OutOfBounds(addr).into())
     }
 
+    /// Reads a little endian u16 without side effects, `None` if
+    /// either byte can't be peeked.
+    fn peek_u16(&self, addr: u16) -> Option<u16> {
+        let next = addr.checked_add(1)?;
+        Some(u16::from_le_bytes([self.peek(addr)?, self.peek(next)?]))
+    }
+
+    /// Writes a u16 as two little endian bytes.
+    ///
+    /// # Errors
+    /// Fails if either byte is outside of the memory.
     fn write_u16(&mut self, addr: u16, value: u16) -> Result<(), DynErr> {
-        let lower = value & 0xff;
-        let upper = (value & 0xff00) >> 8;
+        let [lower, upper] = value.to_le_bytes();
+        let next = addr.checked_add(1).ok_or(Error::OutOfBounds(addr))?;
 
-        self.write(addr, lower as u8)?;
-        self.write(addr + 1, upper as u8)
+        self.write(addr, lower)?;
+        self.write(next, upper)
     }
 
+    /// Copies `n` bytes from one address to another.
+    ///
+    /// # Errors
+    /// Fails if any of the bytes are outside of the memory.
     fn copy(&mut self, from: u16, to: u16, n: usize) -> Result<(), DynErr> {
         for i in 0..n {
-            let val = self.read(from + u16::try_from(i)?)?;
-            self.write(to + u16::try_from(i)?, val)?;
+            let val = self.read(offset(from, i)?)?;
+            self.write(offset(to, i)?, val)?;
         }
 
         Ok(())
     }
 
+    /// Writes a slice of bytes into memory starting at `addr`.
+    ///
+    /// # Errors
+    /// Fails if the slice does not fit in the memory.
     fn load(&mut self, from: &[u8], addr: u16) -> Result<(), DynErr> {
         for (i, byte) in from.iter().enumerate() {
-            self.write(addr + u16::try_from(i)?, *byte)?;
+            self.write(offset(addr, i)?, *byte)?;
         }
 
         Ok(())
     }
 }
 
+/// Adds a byte offset to an address, failing instead of
+/// wrapping around the end of the address space.
+fn offset(addr: u16, i: usize) -> Result<u16, Error> {
+    u16::try_from(i)
+        .ok()
+        .and_then(|i| addr.checked_add(i))
+        .ok_or(Error::OutOfBounds(addr))
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum Error {
@@ -76,11 +142,12 @@ impl std::error::Error for Error {
 /// Linear memory that can have dynamic size
 pub struct Linear {
     bytes: Vec<u8>,
-    size: usize,
+    pub(crate) size: usize,
 }
 
 impl Linear {
     /// Create new linear memory of a certain size
+    #[must_use]
     pub fn new(n: usize) -> Self {
         Self {
             bytes: vec![0; n],
@@ -94,7 +161,7 @@ impl Addressable for Linear {
         let mut result = String::with_capacity(self.bytes.len() * 4);
 
         for chunk in self.bytes.chunks_exact(2) {
-            write!(result, "{:02x}{:02x} ", chunk[0], chunk[1]).unwrap();
+            let _ = write!(result, "{:02x}{:02x} ", chunk[0], chunk[1]);
         }
 
         result
@@ -117,4 +184,22 @@ impl Addressable for Linear {
 
         Ok(())
     }
+
+    fn contents(&self) -> Result<Vec<u8>, DynErr> {
+        Ok(self.bytes.clone())
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> Result<(), DynErr> {
+        if bytes.len() != self.size {
+            return Err(format!(
+                "Expected {} bytes of memory, got {}",
+                self.size,
+                bytes.len()
+            )
+            .into());
+        }
+
+        self.bytes.copy_from_slice(bytes);
+        Ok(())
+    }
 }
@@ -1,10 +1,11 @@
+use crate::address::{Indexed, Offset};
 use crate::register::Register;
 use strawberryvm_derive::VmInstruction;
 
 /// All instructions for the VM. They are automatically
 /// implemented with an encode function to turn them into
 /// binary and also implements From traits.
-#[derive(Debug, VmInstruction)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, VmInstruction)]
 pub enum Instruction {
     #[opcode(0x00)] Nop,                       // No operation    
 
@@ -12,6 +13,8 @@ pub enum Instruction {
     #[opcode(0x11)] Pop(Register),             // Pop top of stack -> register
     #[opcode(0x12)] PushReg(Register),         // Push register value onto stack (does not modify register)
     #[opcode(0x13)] Mov(Register, Register),   // Copy value from second register to first
+    #[opcode(0x14)] LoadImm(Register, u16),    // Load a 16-bit immediate (extension word) into a register
+    #[opcode(0x15)] PushWide(u16),             // Push a 16-bit immediate (extension word) onto the stack
 
     #[opcode(0x20)] Add(Register, Register),   // Add two registers and store in the first
     #[opcode(0x21)] Sub(Register, Register),   // Subtract two registers and store in the first
@@ -28,11 +31,190 @@ pub enum Instruction {
     #[opcode(0x31)] Jmp(i8),                   // Jump by signed offset
     #[opcode(0x32)] Je(i8),                    // Jump if Compare flag is set
     #[opcode(0x33)] Jne(i8),                   // Jump if Compare flag is not set
+    #[opcode(0x34)] Jl(i8),                    // Jump if less, signed
+    #[opcode(0x35)] Jg(i8),                    // Jump if greater, signed
+    #[opcode(0x36)] Jle(i8),                   // Jump if less or equal, signed
+    #[opcode(0x37)] Jge(i8),                   // Jump if greater or equal, signed
+    #[opcode(0x38)] Jb(i8),                    // Jump if below, unsigned
+    #[opcode(0x39)] Ja(i8),                    // Jump if above, unsigned
+    #[opcode(0x3A)] Jc(i8),                    // Jump if Carry flag is set
+    #[opcode(0x3B)] Jo(i8),                    // Jump if Overflow flag is set
+    #[opcode(0x3C)] Jn(i8),                    // Jump if Negative flag is set
+    #[opcode(0x3D)] CmpI(Register, Imm4),      // Compare a register with a 4-bit immediate and set flags
 
-    #[opcode(0x40)] Load(Register, Register),  // Load from memory address in second register into first
-    #[opcode(0x41)] Store(Register, Register), // Store value from first register into memory address in second
+    #[opcode(0x40)] Load(Register, Register),     // Load from memory address in second register into first
+    #[opcode(0x41)] Store(Register, Register),    // Store value from first register into memory address in second
+    #[opcode(0x42)] LoadB(Register, Register),    // Load a byte from address in second register into first, zero-extended
+    #[opcode(0x43)] LoadBS(Register, Register),   // Load a byte from address in second register into first, sign-extended
+    #[opcode(0x44)] StoreB(Register, Register),   // Store low byte of first register into memory address in second
+    #[opcode(0x45)] LoadOff(Register, Offset),    // Load from memory at `[reg+imm]` into first register
+    #[opcode(0x46)] LoadIdx(Register, Indexed),   // Load from memory at `[reg+reg]` into first register
+    #[opcode(0x47)] StoreOff(Register, Offset),   // Store value from first register into memory at `[reg+imm]`
+    #[opcode(0x48)] StoreIdx(Register, Indexed),  // Store value from first register into memory at `[reg+reg]`
+    #[opcode(0x49)] LoadBOff(Register, Offset),   // Load a byte from `[reg+imm]` into first register, zero-extended
+    #[opcode(0x4A)] LoadBIdx(Register, Indexed),  // Load a byte from `[reg+reg]` into first register, zero-extended
+    #[opcode(0x4B)] LoadBSOff(Register, Offset),  // Load a byte from `[reg+imm]` into first register, sign-extended
+    #[opcode(0x4C)] LoadBSIdx(Register, Indexed), // Load a byte from `[reg+reg]` into first register, sign-extended
+    #[opcode(0x4D)] StoreBOff(Register, Offset),  // Store low byte of first register into memory at `[reg+imm]`
+    #[opcode(0x4E)] StoreBIdx(Register, Indexed), // Store low byte of first register into memory at `[reg+reg]`
     
     #[opcode(0x50)] Signal(u8),                // Host call
+
+    #[opcode(0x60)] Call(i8),                  // Push return address and jump by signed offset
+    #[opcode(0x61)] CallReg(Register),         // Push return address and jump to address in register
+    #[opcode(0x62)] Ret,                       // Pop return address into the program counter
+    #[opcode(0x63)] Int(u8),                   // Enter the handler of an interrupt vector
+    #[opcode(0x64)] Iret,                      // Return from an interrupt handler, restoring flags
+    #[opcode(0x65)] Ei,                        // Enable external interrupts
+    #[opcode(0x66)] Di,                        // Disable external interrupts
+
+    #[opcode(0x70)] AddI(Register, Imm4),      // Add a 4-bit immediate to a register
+    #[opcode(0x71)] SubI(Register, Imm4),      // Subtract a 4-bit immediate from a register
+    #[opcode(0x72)] ShlI(Register, Imm4),      // Shift left a register by a 4-bit immediate
+    #[opcode(0x73)] ShrI(Register, Imm4),      // Shift right a register by a 4-bit immediate
+    #[opcode(0x74)] AndI(Register, Imm4),      // Bitwise AND a register with a 4-bit immediate
+    #[opcode(0x75)] OrI(Register, Imm4),       // Bitwise OR a register with a 4-bit immediate
+    #[opcode(0x76)] XorI(Register, Imm4),      // Bitwise XOR a register with a 4-bit immediate
+    #[opcode(0x78)] MulI(Register, Imm4),      // Multiply a register by a 4-bit immediate
+    #[opcode(0x79)] DivI(Register, Imm4),      // Divide a register by a 4-bit immediate
+}
+
+impl Instruction {
+    /// How many cycles the instruction costs to execute. Memory
+    /// accesses, stack traffic and host calls cost more than
+    /// register arithmetic. Entering an interrupt handler costs
+    /// `INTERRUPT_CYCLES` on top of this.
+    #[must_use]
+    pub const fn cycles(&self) -> u64 {
+        match self {
+            Self::Nop
+            | Self::Mov(..)
+            | Self::Add(..)
+            | Self::Sub(..)
+            | Self::Shl(..)
+            | Self::Shr(..)
+            | Self::And(..)
+            | Self::Or(..)
+            | Self::Xor(..)
+            | Self::Not(_)
+            | Self::Cmp(..)
+            | Self::AddI(..)
+            | Self::SubI(..)
+            | Self::ShlI(..)
+            | Self::ShrI(..)
+            | Self::AndI(..)
+            | Self::OrI(..)
+            | Self::XorI(..)
+            | Self::CmpI(..)
+            | Self::Int(_)
+            | Self::Ei
+            | Self::Di => 1,
+
+            Self::Push(_)
+            | Self::PushWide(_)
+            | Self::Pop(_)
+            | Self::PushReg(_)
+            | Self::LoadImm(..)
+            | Self::Jmp(_)
+            | Self::Je(_)
+            | Self::Jne(_)
+            | Self::Jl(_)
+            | Self::Jg(_)
+            | Self::Jle(_)
+            | Self::Jge(_)
+            | Self::Jb(_)
+            | Self::Ja(_)
+            | Self::Jc(_)
+            | Self::Jo(_)
+            | Self::Jn(_) => 2,
+
+            Self::Mul(..)
+            | Self::MulI(..)
+            | Self::Load(..)
+            | Self::Store(..)
+            | Self::LoadB(..)
+            | Self::LoadBS(..)
+            | Self::StoreB(..)
+            | Self::LoadOff(..)
+            | Self::LoadIdx(..)
+            | Self::StoreOff(..)
+            | Self::StoreIdx(..)
+            | Self::LoadBOff(..)
+            | Self::LoadBIdx(..)
+            | Self::LoadBSOff(..)
+            | Self::LoadBSIdx(..)
+            | Self::StoreBOff(..)
+            | Self::StoreBIdx(..) => 3,
+            Self::Call(_) | Self::CallReg(_) | Self::Ret => 4,
+            Self::Signal(_) | Self::Iret => 5,
+            Self::Div(..) | Self::DivI(..) => 8,
+        }
+    }
+}
+
+/// An unsigned 4-bit immediate, `0` to `15`, small enough to share the
+/// first word with a register operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Imm4(u8);
+
+impl Imm4 {
+    /// Returns `None` if the value does not fit in 4 bits.
+    #[must_use]
+    pub const fn new(value: u8) -> Option<Self> {
+        if value < 16 {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<Imm4> for u16 {
+    fn from(imm: Imm4) -> Self {
+        Self::from(imm.0)
+    }
+}
+
+impl TryFrom<i64> for Imm4 {
+    type Error = ();
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        u8::try_from(value).ok().and_then(Self::new).ok_or(())
+    }
+}
+
+impl std::fmt::Display for Imm4 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Returned when a word can not be decoded into an `Instruction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownOpcode(u8),
+    BadRegister(u8),
+    /// The opcode belongs to a wide instruction but only one word was given.
+    MissingExtension(u8),
+}
+
+impl std::error::Error for DecodeError {}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownOpcode(op) => write!(f, "Unknown opcode 0x{op:02X}"),
+            Self::BadRegister(r) => write!(f, "Invalid register {r}"),
+            Self::MissingExtension(op) => {
+                write!(f, "Opcode 0x{op:02X} is missing its extension word")
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1,24 +1,45 @@
-use std::sync::{Mutex, OnceLock};
+use std::cell::Cell;
 
-static LAST_STATUS: OnceLock<Mutex<String>> = OnceLock::new();
+use crate::vm::{status_table, REGISTER_COUNT};
 
-pub fn set_last_status(status: String) {
-    let lock = LAST_STATUS.get_or_init(|| Mutex::new(String::new()));
+/// The instruction a machine last started, kept so that its state can
+/// be described if the host panics while running it.
+#[derive(Clone, Copy)]
+struct LastInstruction {
+    pc: u16,
+    instruction: u16,
+    registers: [u16; REGISTER_COUNT],
+}
+
+thread_local! {
+    static LAST_INSTRUCTION: Cell<Option<LastInstruction>> = const { Cell::new(None) };
+}
 
-    match lock.lock() {
-        Ok(mut guard) => *guard = status,
-        Err(poisoned) => {
-            // If the mutex is poisoned due to a previous panic, still try to record.
-            *poisoned.into_inner() = status;
-        }
-    }
+/// Remembers the instruction being started, called on every step. This
+/// only copies a few words, the report is formatted by `get_last_status`.
+pub(crate) fn record(pc: u16, instruction: u16, registers: &[u16; REGISTER_COUNT]) {
+    LAST_INSTRUCTION.with(|last| {
+        last.set(Some(LastInstruction {
+            pc,
+            instruction,
+            registers: *registers,
+        }));
+    });
 }
 
+/// Describes the instruction a machine on this thread last started and
+/// the registers at the time, or `None` if no machine has run on it.
+///
+/// Meant to be called from a panic hook, which runs on the thread that
+/// panicked.
+#[must_use]
 pub fn get_last_status() -> Option<String> {
-    let lock = LAST_STATUS.get()?;
+    let last = LAST_INSTRUCTION.with(Cell::get)?;
 
-    match lock.lock() {
-        Ok(guard) => Some(guard.clone()),
-        Err(poisoned) => Some(poisoned.into_inner().clone()),
-    }
+    Some(format!(
+        "PC: 0x{:04X}\nINSTR: 0x{:04X}\n{}",
+        last.pc,
+        last.instruction,
+        status_table(&last.registers)
+    ))
 }
@@ -0,0 +1,200 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::op::Instruction;
+use crate::trace::{TraceRecord, TraceSink};
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// How often something was executed and the cycles it took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counter {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+impl Counter {
+    fn add(&mut self, cycles: u64) {
+        self.count += 1;
+        self.cycles += cycles;
+    }
+}
+
+/// Statistics for one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressStats {
+    /// The instruction last executed at the address.
+    pub instruction: Instruction,
+    pub counter: Counter,
+}
+
+/// Execution counts gathered by a `Profiler`.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    addresses: BTreeMap<u16, AddressStats>,
+    opcodes: BTreeMap<u8, (String, Counter)>,
+
+    /// Cycles spent in each call stack, as entry addresses from the
+    /// outermost frame inwards.
+    stacks: BTreeMap<Vec<u16>, u64>,
+    stack: Vec<u16>,
+    entering_call: bool,
+    /// Where the last instruction left `PC`, an instruction elsewhere
+    /// means an interrupt or trapped fault entered its handler.
+    next_pc: Option<u16>,
+}
+
+impl Profile {
+    fn record(&mut self, pc: u16, instruction: Instruction, next_pc: u16) {
+        let cycles = instruction.cycles();
+        let interrupted = self.next_pc.is_some_and(|next| next != pc);
+
+        if self.stack.is_empty() || self.entering_call || interrupted {
+            self.stack.push(pc);
+            self.entering_call = false;
+        }
+
+        self.next_pc = Some(next_pc);
+
+        self.addresses
+            .entry(pc)
+            .and_modify(|stats| stats.instruction = instruction)
+            .or_insert_with(|| AddressStats {
+                instruction,
+                counter: Counter::default(),
+            })
+            .counter
+            .add(cycles);
+
+        let [opcode, _] = instruction.encode_u16().to_le_bytes();
+        self.opcodes
+            .entry(opcode)
+            .or_insert_with(|| {
+                let name = instruction.to_string();
+                let mnemonic = name.split_whitespace().next().unwrap_or_default();
+                (mnemonic.to_string(), Counter::default())
+            })
+            .1
+            .add(cycles);
+
+        match self.stacks.get_mut(self.stack.as_slice()) {
+            Some(total) => *total += cycles,
+            None => {
+                self.stacks.insert(self.stack.clone(), cycles);
+            }
+        }
+
+        match instruction {
+            Instruction::Call(_) | Instruction::CallReg(_) | Instruction::Int(_) => {
+                self.entering_call = true;
+            }
+
+            Instruction::Ret | Instruction::Iret if self.stack.len() > 1 => {
+                self.stack.pop();
+            }
+
+            _ => (),
+        }
+    }
+
+    /// Total instructions and cycles recorded.
+    #[must_use]
+    pub fn total(&self) -> Counter {
+        self.addresses
+            .values()
+            .fold(Counter::default(), |total, stats| Counter {
+                count: total.count + stats.counter.count,
+                cycles: total.cycles + stats.counter.cycles,
+            })
+    }
+
+    /// Every executed address with its statistics, in address order.
+    pub fn addresses(&self) -> impl Iterator<Item = (u16, &AddressStats)> + '_ {
+        self.addresses
+            .iter()
+            .map(|(address, stats)| (*address, stats))
+    }
+
+    /// The `n` addresses that took the most cycles, hottest first.
+    #[must_use]
+    pub fn hottest(&self, n: usize) -> Vec<(u16, AddressStats)> {
+        let mut addresses: Vec<_> = self.addresses.iter().map(|(a, s)| (*a, *s)).collect();
+        addresses.sort_by(|(a, x), (b, y)| y.counter.cycles.cmp(&x.counter.cycles).then(a.cmp(b)));
+        addresses.truncate(n);
+        addresses
+    }
+
+    /// Statistics per opcode, named by mnemonic, most cycles first.
+    #[must_use]
+    pub fn opcodes(&self) -> Vec<(&str, Counter)> {
+        let mut opcodes: Vec<_> = self
+            .opcodes
+            .values()
+            .map(|(name, counter)| (name.as_str(), *counter))
+            .collect();
+
+        opcodes.sort_by(|(a, x), (b, y)| y.cycles.cmp(&x.cycles).then(a.cmp(b)));
+        opcodes
+    }
+
+    /// Cycles per call stack in the folded format read by flamegraph
+    /// tools, one `outer;inner cycles` line per stack. Calls are tracked
+    /// through `Call`, `CallReg`, `Int`, `Ret` and `Iret`, interrupts and
+    /// trapped faults also start a frame. Frames are named by `name`,
+    /// given the entry address of each frame.
+    pub fn folded(&self, name: impl Fn(u16) -> String) -> String {
+        let mut result = String::new();
+
+        for (stack, cycles) in &self.stacks {
+            let frames: Vec<String> = stack.iter().map(|address| name(*address)).collect();
+            let _ = writeln!(result, "{} {cycles}", frames.join(";"));
+        }
+
+        result
+    }
+}
+
+/// A `TraceSink` that builds a `Profile`. It can be cloned before being
+/// given to a machine to read the profile afterwards.
+///
+/// ```rust
+/// use strawberryvm::prelude::*;
+/// use strawberryvm::profile::Profiler;
+///
+/// let profiler = Profiler::new();
+///
+/// let mut vm = Machine::new();
+/// vm.set_trace_sink(Box::new(profiler.clone()));
+/// vm.step().unwrap();
+///
+/// assert_eq!(profiler.profile().total().count, 1);
+/// ```
+#[derive(Clone, Default)]
+pub struct Profiler {
+    profile: Rc<RefCell<Profile>>,
+}
+
+impl Profiler {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the profile gathered so far.
+    #[must_use]
+    pub fn profile(&self) -> Profile {
+        self.profile.borrow().clone()
+    }
+}
+
+impl TraceSink for Profiler {
+    fn record(&mut self, record: &TraceRecord) -> Result<(), DynErr> {
+        self.profile
+            .borrow_mut()
+            .record(record.pc, record.instruction, record.next_pc);
+
+        Ok(())
+    }
+}
@@ -1,14 +1,28 @@
 use strawberryvm_derive::{Display, FromStr, FromU8};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Flag {
     Compare = 1 << 0,
     Negative = 1 << 1,
     Overflow = 1 << 2,
+    InterruptEnable = 1 << 3,
+    Carry = 1 << 4,
+}
+
+impl Flag {
+    /// Every flag, in bit order.
+    pub const ALL: [Self; 5] = [
+        Self::Compare,
+        Self::Negative,
+        Self::Overflow,
+        Self::InterruptEnable,
+        Self::Carry,
+    ];
 }
 
 /// Enum for registers, only really used
 /// to co-ordinate the register slice.
-#[derive(Debug, Clone, Copy, FromU8, Display, FromStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromU8, Display, FromStr)]
 pub enum Register {
     A,  // General purpose
     B,  // General purpose
@@ -0,0 +1,153 @@
+use std::io::{Read, Write};
+
+use crate::vm::REGISTER_COUNT;
+
+/// The first bytes of every snapshot file.
+pub const MAGIC: [u8; 4] = *b"SVMS";
+
+/// Version of the snapshot format written by this crate.
+pub const VERSION: u16 = 1;
+
+/// Returned when a snapshot can not be read.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The data does not start with `MAGIC`.
+    BadMagic,
+    /// The snapshot was written by a newer or unknown format version.
+    UnsupportedVersion(u16),
+    /// The data ended before the snapshot did.
+    Truncated,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a snapshot"),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported snapshot version {v}"),
+            Self::Truncated => write!(f, "Snapshot is truncated"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// The full state of a `Machine`, taken with `Machine::snapshot` and
+/// applied with `Machine::restore`. Signal handlers and the debug
+/// flag are host configuration, so they are not part of it.
+///
+/// On disk, every number is little endian:
+///
+/// | Size             | Field                               |
+/// |------------------|-------------------------------------|
+/// | 4                | `MAGIC`                             |
+/// | 2                | `VERSION`                           |
+/// | 2 * 8            | Registers, in `Register` order      |
+/// | 1                | 1 if the machine is halted, else 0  |
+/// | 8                | Cycles                              |
+/// | 2                | Interrupt table address             |
+/// | 2                | Pending interrupts, one bit each    |
+/// | 4                | Length of memory                    |
+/// | Length of memory | Memory                              |
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub registers: [u16; REGISTER_COUNT],
+    pub machine_halted: bool,
+    pub cycles: u64,
+    pub interrupt_table: u16,
+    pub pending_interrupts: u16,
+    pub memory: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Writes the snapshot in the on-disk format.
+    ///
+    /// # Errors
+    /// Fails if the writer fails.
+    pub fn write_to(&self, mut out: impl Write) -> Result<(), SnapshotError> {
+        let length = u32::try_from(self.memory.len())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Memory is too large"))?;
+
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+
+        for register in self.registers {
+            out.write_all(&register.to_le_bytes())?;
+        }
+
+        out.write_all(&[u8::from(self.machine_halted)])?;
+        out.write_all(&self.cycles.to_le_bytes())?;
+        out.write_all(&self.interrupt_table.to_le_bytes())?;
+        out.write_all(&self.pending_interrupts.to_le_bytes())?;
+
+        out.write_all(&length.to_le_bytes())?;
+        out.write_all(&self.memory)?;
+
+        Ok(())
+    }
+
+    /// Reads a snapshot in the on-disk format.
+    ///
+    /// # Errors
+    /// Fails if the data is not a snapshot of a supported version.
+    pub fn read_from(mut input: impl Read) -> Result<Self, SnapshotError> {
+        let mut magic = [0; 4];
+        read_exact(&mut input, &mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut input)?);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut registers = [0; REGISTER_COUNT];
+        for register in &mut registers {
+            *register = u16::from_le_bytes(read_array(&mut input)?);
+        }
+
+        let [halted] = read_array(&mut input)?;
+        let cycles = u64::from_le_bytes(read_array(&mut input)?);
+        let interrupt_table = u16::from_le_bytes(read_array(&mut input)?);
+        let pending_interrupts = u16::from_le_bytes(read_array(&mut input)?);
+
+        // Read through `take` so a corrupt length can't allocate more than the data.
+        let length = u32::from_le_bytes(read_array(&mut input)?);
+        let mut memory = Vec::new();
+        input.take(u64::from(length)).read_to_end(&mut memory)?;
+
+        if memory.len() != length as usize {
+            return Err(SnapshotError::Truncated);
+        }
+
+        Ok(Self {
+            registers,
+            machine_halted: halted != 0,
+            cycles,
+            interrupt_table,
+            pending_interrupts,
+            memory,
+        })
+    }
+}
+
+fn read_exact(input: &mut impl Read, buf: &mut [u8]) -> Result<(), SnapshotError> {
+    input.read_exact(buf).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => SnapshotError::Truncated,
+        _ => SnapshotError::Io(e),
+    })
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> Result<[u8; N], SnapshotError> {
+    let mut buf = [0; N];
+    read_exact(input, &mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,214 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::error::Fault;
+use crate::op::Instruction;
+use crate::register::{Flag, Register};
+use crate::vm::REGISTER_COUNT;
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// What one instruction did, handed to a `TraceSink` after it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Address of the instruction.
+    pub pc: u16,
+    pub instruction: Instruction,
+    /// Where `PC` was left, the address of the next instruction to run.
+    pub next_pc: u16,
+    /// Registers the instruction changed, with their old and new values.
+    /// `PC` and `FL` are left out, see `flags` for the latter.
+    pub registers: Vec<(Register, u16, u16)>,
+    /// Flags the instruction changed, with their new state.
+    pub flags: Vec<(Flag, bool)>,
+    /// Words read from memory as `(address, value)`.
+    pub reads: Vec<(u16, u16)>,
+    /// Words written to memory as `(address, value)`.
+    pub writes: Vec<(u16, u16)>,
+    /// Bytes read from memory by `LoadB` and `LoadBS`.
+    pub byte_reads: Vec<(u16, u8)>,
+    /// Bytes written to memory by `StoreB`.
+    pub byte_writes: Vec<(u16, u8)>,
+    /// Description of the fault the instruction raised, if any.
+    pub fault: Option<String>,
+}
+
+/// Memory accessed by the instruction being traced.
+#[derive(Default)]
+pub(crate) struct Accesses {
+    pub reads: Vec<(u16, u16)>,
+    pub writes: Vec<(u16, u16)>,
+    pub byte_reads: Vec<(u16, u8)>,
+    pub byte_writes: Vec<(u16, u8)>,
+}
+
+impl TraceRecord {
+    pub(crate) fn new(
+        pc: u16,
+        instruction: Instruction,
+        before: &[u16; REGISTER_COUNT],
+        after: &[u16; REGISTER_COUNT],
+        accesses: Accesses,
+        result: &Result<(), Fault>,
+    ) -> Self {
+        let registers = (0u8..)
+            .zip(before.iter().zip(after))
+            .filter(|(_, (old, new))| old != new)
+            .filter_map(|(i, (old, new))| Some((Register::try_from(i).ok()?, *old, *new)))
+            .filter(|(r, _, _)| !matches!(r, Register::PC | Register::FL))
+            .collect();
+
+        let (old_flags, new_flags) = (before[Register::FL as usize], after[Register::FL as usize]);
+        let flags = Flag::ALL
+            .into_iter()
+            .filter(|flag| (old_flags ^ new_flags) & *flag as u16 != 0)
+            .map(|flag| (flag, new_flags & flag as u16 != 0))
+            .collect();
+
+        Self {
+            pc,
+            instruction,
+            next_pc: after[Register::PC as usize],
+            registers,
+            flags,
+            reads: accesses.reads,
+            writes: accesses.writes,
+            byte_reads: accesses.byte_reads,
+            byte_writes: accesses.byte_writes,
+            fault: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+}
+
+/// Receives a `TraceRecord` for every instruction a machine executes.
+/// Set one with `Machine::set_trace_sink`.
+pub trait TraceSink {
+    /// # Errors
+    /// A failure stops the machine with `Fault::TraceFailed`.
+    fn record(&mut self, record: &TraceRecord) -> Result<(), DynErr>;
+
+    /// Called by the host once tracing is finished.
+    ///
+    /// # Errors
+    /// Fails if buffered records can not be written.
+    fn flush(&mut self) -> Result<(), DynErr> {
+        Ok(())
+    }
+}
+
+/// Writes each record as one line of JSON, for example:
+///
+/// ```text
+/// {"pc":4,"op":"Store A B","regs":{},"flags":{},"reads":[],"writes":[[256,4660]]}
+/// ```
+///
+/// Registers map to `[old, new]` and flags to their new state. Byte
+/// accesses are added as `"byte_reads"` and `"byte_writes"` when there
+/// are any, and a `"fault"` string when the instruction faulted.
+pub struct JsonLines<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub const fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Gives back the writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(result, "\\u{:04x}", u32::from(c));
+            }
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+fn json_pairs<T: std::fmt::Display>(pairs: &[(u16, T)]) -> String {
+    let pairs: Vec<String> = pairs.iter().map(|(a, v)| format!("[{a},{v}]")).collect();
+    format!("[{}]", pairs.join(","))
+}
+
+impl TraceRecord {
+    /// The record as a single line of JSON, without a newline.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .map(|(r, old, new)| format!("\"{r}\":[{old},{new}]"))
+            .collect();
+
+        let flags: Vec<String> = self
+            .flags
+            .iter()
+            .map(|(flag, set)| format!("\"{flag:?}\":{set}"))
+            .collect();
+
+        let mut line = format!(
+            "{{\"pc\":{},\"op\":{},\"regs\":{{{}}},\"flags\":{{{}}},\"reads\":{},\"writes\":{}",
+            self.pc,
+            json_string(&self.instruction.to_string()),
+            registers.join(","),
+            flags.join(","),
+            json_pairs(&self.reads),
+            json_pairs(&self.writes),
+        );
+
+        if !self.byte_reads.is_empty() {
+            let _ = write!(line, ",\"byte_reads\":{}", json_pairs(&self.byte_reads));
+        }
+
+        if !self.byte_writes.is_empty() {
+            let _ = write!(line, ",\"byte_writes\":{}", json_pairs(&self.byte_writes));
+        }
+
+        if let Some(fault) = &self.fault {
+            let _ = write!(line, ",\"fault\":{}", json_string(fault));
+        }
+
+        line.push('}');
+        line
+    }
+}
+
+impl<W: Write> TraceSink for JsonLines<W> {
+    fn record(&mut self, record: &TraceRecord) -> Result<(), DynErr> {
+        writeln!(self.out, "{}", record.to_json())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), DynErr> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Sends every record to several sinks, in order.
+#[derive(Default)]
+pub struct Tee(pub Vec<Box<dyn TraceSink>>);
+
+impl TraceSink for Tee {
+    fn record(&mut self, record: &TraceRecord) -> Result<(), DynErr> {
+        self.0.iter_mut().try_for_each(|sink| sink.record(record))
+    }
+
+    fn flush(&mut self) -> Result<(), DynErr> {
+        self.0.iter_mut().try_for_each(|sink| sink.flush())
+    }
+}
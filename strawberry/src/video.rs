@@ -0,0 +1,321 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::bus::Device;
+use crate::memory::Error;
+
+type DynErr = Box<dyn std::error::Error>;
+
+/// Width of the screen in pixels.
+pub const WIDTH: usize = 64;
+/// Height of the screen in pixels.
+pub const HEIGHT: usize = 48;
+
+/// Colours of the fixed 16 colour palette as RGB.
+pub const PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], // Black
+    [0x1D, 0x2B, 0x53], // Dark blue
+    [0x7E, 0x25, 0x53], // Dark purple
+    [0x00, 0x87, 0x51], // Dark green
+    [0xAB, 0x52, 0x36], // Brown
+    [0x5F, 0x57, 0x4F], // Dark grey
+    [0xC2, 0xC3, 0xC7], // Light grey
+    [0xFF, 0xF1, 0xE8], // White
+    [0xFF, 0x00, 0x4D], // Red
+    [0xFF, 0xA3, 0x00], // Orange
+    [0xFF, 0xEC, 0x27], // Yellow
+    [0x00, 0xE4, 0x36], // Green
+    [0x29, 0xAD, 0xFF], // Blue
+    [0x83, 0x76, 0x9C], // Lavender
+    [0xFF, 0x77, 0xA8], // Pink
+    [0xFF, 0xCC, 0xAA], // Peach
+];
+
+/// A presented frame, one palette index per pixel in rows from the
+/// top left. Only the low 4 bits of an index select a colour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub pixels: Vec<u8>,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            pixels: vec![0; WIDTH * HEIGHT],
+        }
+    }
+}
+
+impl Frame {
+    /// Palette index of the pixel at `x`, `y`.
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= WIDTH {
+            return None;
+        }
+
+        self.pixels.get(y * WIDTH + x).copied()
+    }
+
+    /// The frame as RGB bytes, three per pixel.
+    #[must_use]
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|index| PALETTE[usize::from(index & 0x0F)])
+            .collect()
+    }
+
+    /// Writes the frame as a binary PPM (P6) image.
+    ///
+    /// # Errors
+    /// Fails if the writer fails.
+    pub fn write_ppm(&self, mut out: impl Write) -> std::io::Result<()> {
+        write!(out, "P6\n{WIDTH} {HEIGHT}\n255\n")?;
+        out.write_all(&self.to_rgb())
+    }
+
+    /// Writes the frame as an uncompressed RGB PNG image.
+    ///
+    /// # Errors
+    /// Fails if the writer fails.
+    pub fn write_png(&self, mut out: impl Write) -> std::io::Result<()> {
+        let rgb = self.to_rgb();
+
+        // Every row starts with filter type 0 (none).
+        let mut raw = Vec::with_capacity(HEIGHT * (WIDTH * 3 + 1));
+        for row in rgb.chunks(WIDTH * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&png_u32(WIDTH));
+        header.extend_from_slice(&png_u32(HEIGHT));
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_chunk(&mut out, *b"IHDR", &header)?;
+        write_chunk(&mut out, *b"IDAT", &zlib_stored(&raw))?;
+        write_chunk(&mut out, *b"IEND", &[])
+    }
+}
+
+fn png_u32(n: usize) -> [u8; 4] {
+    u32::try_from(n).unwrap_or(u32::MAX).to_be_bytes()
+}
+
+fn write_chunk(out: &mut impl Write, kind: [u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&png_u32(data.len()))?;
+    out.write_all(&kind)?;
+    out.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = u32::MAX;
+
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(usize::from(u16::MAX)).peekable();
+
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let len = u16::try_from(block.len()).unwrap_or(u16::MAX);
+
+        out.push(u8::from(blocks.peek().is_none()));
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+#[derive(Default)]
+struct Buffers {
+    back: Frame,
+    front: Frame,
+    presented: u64,
+}
+
+impl Buffers {
+    fn present(&mut self) {
+        self.front.clone_from(&self.back);
+        self.presented += 1;
+    }
+}
+
+/// Host side handle to a `Framebuffer`. It stays usable after the
+/// framebuffer has been attached to a `Bus`.
+#[derive(Clone, Default)]
+pub struct Screen {
+    buffers: Rc<RefCell<Buffers>>,
+}
+
+impl Screen {
+    /// The last frame that was presented.
+    #[must_use]
+    pub fn frame(&self) -> Frame {
+        self.buffers.borrow().front.clone()
+    }
+
+    /// Amount of frames presented so far.
+    #[must_use]
+    pub fn frames_presented(&self) -> u64 {
+        self.buffers.borrow().presented
+    }
+
+    /// Presents the back buffer, as if the guest wrote to `CONTROL`.
+    pub fn present(&self) {
+        self.buffers.borrow_mut().present();
+    }
+}
+
+/// Memory mapped video card with a `WIDTH` by `HEIGHT` screen. Programs
+/// draw into a back buffer with one palette index per byte, then write
+/// to the control port to present it (vsync).
+///
+/// | Offset            | Read                                   | Write                   |
+/// |-------------------|----------------------------------------|-------------------------|
+/// | 0 to `PIXELS` - 1 | Back buffer pixel                      | Back buffer pixel       |
+/// | `CONTROL`         | Frames presented, low byte (wrapping)  | Present the back buffer |
+/// | `CONTROL` + 1     | Frames presented, high byte (wrapping) | Ignored                 |
+///
+/// Storing a word to `CONTROL` presents once.
+///
+/// ```rust
+/// use strawberryvm::prelude::*;
+/// use strawberryvm::bus::Bus;
+/// use strawberryvm::video::Framebuffer;
+///
+/// let framebuffer = Framebuffer::new();
+/// let screen = framebuffer.screen();
+///
+/// let mut bus = Bus::new();
+/// bus.attach(0x0000, Linear::new(0x1000)).unwrap();
+/// bus.attach(0x8000, framebuffer).unwrap();
+///
+/// let mut vm = Machine::with_memory(Box::new(bus));
+///
+/// // Also present when the guest raises `Signal $F4`.
+/// let vsync = screen.clone();
+/// vm.define_handler(0xF4, move |_| {
+///     vsync.present();
+///     Ok(SignalAction::Continue)
+/// });
+/// ```
+#[derive(Default)]
+pub struct Framebuffer {
+    buffers: Rc<RefCell<Buffers>>,
+}
+
+impl Framebuffer {
+    #[allow(clippy::cast_possible_truncation)]
+    pub const PIXELS: u16 = (WIDTH * HEIGHT) as u16;
+    pub const CONTROL: u16 = Self::PIXELS;
+    const STATUS_HIGH: u16 = Self::CONTROL + 1;
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a handle the host can read presented frames through.
+    #[must_use]
+    pub fn screen(&self) -> Screen {
+        Screen {
+            buffers: Rc::clone(&self.buffers),
+        }
+    }
+}
+
+impl Device for Framebuffer {
+    fn name(&self) -> &'static str {
+        "Framebuffer"
+    }
+
+    fn size(&self) -> usize {
+        WIDTH * HEIGHT + 2
+    }
+
+    fn read(&mut self, offset: u16) -> Result<u8, DynErr> {
+        let buffers = self.buffers.borrow();
+
+        match offset {
+            Self::CONTROL => Ok(buffers.presented.to_le_bytes()[0]),
+            Self::STATUS_HIGH => Ok(buffers.presented.to_le_bytes()[1]),
+
+            _ => buffers
+                .back
+                .pixels
+                .get(usize::from(offset))
+                .copied()
+                .ok_or_else(|| Error::OutOfBounds(offset).into()),
+        }
+    }
+
+    fn peek(&self, offset: u16) -> Option<u8> {
+        self.buffers
+            .borrow()
+            .back
+            .pixels
+            .get(usize::from(offset))
+            .copied()
+    }
+
+    fn write(&mut self, offset: u16, value: u8) -> Result<(), DynErr> {
+        let mut buffers = self.buffers.borrow_mut();
+
+        match offset {
+            Self::CONTROL => {
+                buffers.present();
+                return Ok(());
+            }
+
+            Self::STATUS_HIGH => return Ok(()),
+
+            _ => (),
+        }
+
+        let pixel = buffers
+            .back
+            .pixels
+            .get_mut(usize::from(offset))
+            .ok_or(Error::OutOfBounds(offset))?;
+
+        *pixel = value;
+        Ok(())
+    }
+
+    fn dump(&self) -> String {
+        format!("{} frames presented", self.buffers.borrow().presented)
+    }
+}
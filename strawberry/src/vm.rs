@@ -1,15 +1,128 @@
+use crate::error::{Fault, VmError};
 use crate::op::Instruction;
 use crate::prelude::Flag;
 use crate::register::Register;
 use std::collections::HashMap;
 
+use crate::decode_cache::{DecodeCache, Decoded};
+use crate::journal::{Entry, Journal};
 use crate::memory;
 use crate::panic_report;
+use crate::snapshot::Snapshot;
+use crate::trace::{Accesses, TraceRecord, TraceSink};
 
 pub const MEMORY_KILO_BYTES: usize = 1;
 pub const REGISTER_COUNT: usize = 8;
 
-type SignalFunction = fn(&mut Machine);
+/// The most memory a machine can address with its 16-bit addresses.
+pub const MAX_MEMORY_BYTES: usize = 1 << 16;
+
+/// Number of entries in the interrupt vector table. Each entry is
+/// the u16 address of a handler, or 0 when no handler is installed.
+pub const INTERRUPT_VECTORS: u8 = 16;
+
+/// Where the interrupt vector table goes when the memory size is not
+/// known, the last 32 bytes of the address space.
+pub const DEFAULT_INTERRUPT_TABLE: u16 = 0xFFE0;
+
+/// Cycles spent entering an interrupt handler, whether from `Int`,
+/// a fault or an external interrupt.
+pub const INTERRUPT_CYCLES: u64 = 5;
+
+/// Peeks and decodes the instruction at `address` without side effects
+/// on the machine or its devices, or `None` if it can't be peeked or is
+/// not a valid instruction.
+fn decode_at(memory: &dyn memory::Addressable, address: u16) -> Option<Decoded> {
+    let word = memory.peek_u16(address)?;
+    let size = Instruction::encoded_size(word);
+
+    let extension = if size > 2 {
+        memory.peek_u16(address.checked_add(2)?)?
+    } else {
+        0
+    };
+
+    Some(Decoded {
+        word,
+        instruction: Instruction::decode(word, extension).ok()?,
+        size,
+    })
+}
+
+/// Places the interrupt vector table in the last 32 bytes of memory.
+fn interrupt_table_for(memory_size: usize) -> u16 {
+    let table_size = usize::from(INTERRUPT_VECTORS) * 2;
+
+    memory_size
+        .checked_sub(table_size)
+        .and_then(|addr| u16::try_from(addr).ok())
+        .unwrap_or(0)
+}
+
+/// Formats registers, in `Register` order, as the table shown by
+/// `Machine::status`.
+#[must_use]
+pub fn status_table(registers: &[u16; REGISTER_COUNT]) -> String {
+    let width = 5;
+
+    let [a, b, c, m, sp, pc, bp, flags] = *registers;
+
+    let line_width = (width + 3) * 8 - 1;
+    let lines = [
+        String::new(),
+        format!("   {:^line_width$}", "» Registers «"),
+        format!(" ┌{:─<line_width$}┐",""),
+        format!(" │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │", "A", "B", "C", "M", "SP", "PC", "BP", "FLAGS"),
+        format!(" │ {a:^width$} │ {b:^width$} │ {c:^width$} │ {m:^width$} │ {sp:^width$} │ {pc:^width$} │ {bp:^width$} │ {flags:^width$} │"),
+        format!(" └{:─<line_width$}┘", ""),
+        String::new(),
+    ];
+
+    lines.join("\n")
+}
+
+/// Why `Machine::run_for` returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The machine halted.
+    Halted,
+    /// The cycle budget ran out before the machine halted.
+    BudgetExhausted,
+}
+
+impl std::fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Halted => write!(f, "Halted"),
+            Self::BudgetExhausted => write!(f, "Budget exhausted"),
+        }
+    }
+}
+
+/// What the machine should do after a signal handler returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalAction {
+    /// Carry on with the next instruction.
+    Continue,
+    /// Halt the machine.
+    Halt,
+}
+
+/// Returned by signal handlers. An error stops the machine with a
+/// `Fault::SignalFailed`.
+pub type SignalResult = Result<SignalAction, Box<dyn std::error::Error>>;
+
+/// A signal handler, which may capture host state.
+pub type SignalHandler = Box<dyn FnMut(&mut Machine) -> SignalResult>;
+
+/// The general purpose registers, which signals use to take arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalArgs {
+    pub a: u16,
+    pub b: u16,
+    pub c: u16,
+    pub d: u16,
+}
 
 /// The main structure for the VM. This can be created
 /// and essentially is all you need to get going.
@@ -21,7 +134,20 @@ pub struct Machine {
     pub memory: Box<dyn memory::Addressable>,
     registers: [u16; REGISTER_COUNT],
 
-    signal_handlers: HashMap<u8, SignalFunction>,
+    signal_handlers: HashMap<u8, SignalHandler>,
+    /// The signal whose handler is running, and whether it was removed.
+    running_handler: Option<(u8, bool)>,
+
+    /// Address of the interrupt vector table.
+    pub interrupt_table: u16,
+    pending_interrupts: u16,
+
+    cycles: u64,
+    journal: Option<Journal>,
+    decode_cache: Option<DecodeCache>,
+
+    trace_sink: Option<Box<dyn TraceSink>>,
+    trace_accesses: Option<Accesses>,
 
     pub debug: bool,
     pub machine_halted: bool,
@@ -38,51 +164,52 @@ impl Machine {
     /// based on the constants set in the file.
     #[must_use]
     pub fn new() -> Self {
+        let mut machine =
+            Self::with_memory(Box::new(memory::Linear::new(MEMORY_KILO_BYTES * 1024)));
+
+        machine.interrupt_table = interrupt_table_for(MEMORY_KILO_BYTES * 1024);
+        machine
+    }
+
+    /// Creates a new instance of a Machine that uses the given memory
+    /// rather than the default linear memory.
+    #[must_use]
+    pub fn with_memory(memory: Box<dyn memory::Addressable>) -> Self {
         Self {
             registers: [0; REGISTER_COUNT],
-            memory: Box::new(memory::Linear::new(MEMORY_KILO_BYTES * 1024)),
+            memory,
 
             signal_handlers: HashMap::new(),
+            running_handler: None,
             machine_halted: false,
 
+            interrupt_table: DEFAULT_INTERRUPT_TABLE,
+            pending_interrupts: 0,
+
+            cycles: 0,
+            journal: None,
+            decode_cache: None,
+
+            trace_sink: None,
+            trace_accesses: None,
+
             debug: false,
         }
     }
 
+    /// Returns a builder to configure the memory and initial
+    /// registers of a machine.
+    #[must_use]
+    pub const fn builder() -> MachineBuilder {
+        MachineBuilder::new()
+    }
+
     /// Returns a table of each register as a string
     /// This is only really useful for debugging and
     /// is not really useful for anything else.
     #[must_use]
     pub fn status(&self) -> String {
-        let width = 5;
-
-        let (a, b, c, m) = (
-            self.get_register(Register::A),
-            self.get_register(Register::B),
-            self.get_register(Register::C),
-            self.get_register(Register::D),
-        );
-
-        let (sp, pc, bp) = (
-            self.get_register(Register::SP),
-            self.get_register(Register::PC),
-            self.get_register(Register::BP),
-        );
-
-        let flags = self.get_register(Register::FL);
-
-        let line_width = (width + 3) * 8 - 1;
-        let lines = vec![
-            String::new(),
-            format!("   {:^line_width$}", "» Registers «"),
-            format!(" ┌{:─<line_width$}┐",""),
-            format!(" │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │", "A", "B", "C", "M", "SP", "PC", "BP", "FLAGS"),
-            format!(" │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │ {:^width$} │", a, b, c, m, sp, pc, bp, flags),
-            format!(" └{:─<line_width$}┘", ""),
-            String::new(),
-        ];
-
-        lines.join("\n")
+        status_table(&self.registers)
     }
 
     /// Returns the value of of a register inside of the machine
@@ -93,10 +220,441 @@ impl Machine {
         self.registers[r as usize]
     }
 
+    /// Sets the value of a register, for signal handlers that
+    /// return results to the guest.
+    pub fn set_register(&mut self, r: Register, value: u16) {
+        self.registers[r as usize] = value;
+    }
+
+    /// Captures the registers, memory and execution state of the machine.
+    ///
+    /// # Errors
+    /// Fails if the memory does not support being saved.
+    pub fn snapshot(&self) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        Ok(Snapshot {
+            registers: self.registers,
+            machine_halted: self.machine_halted,
+            cycles: self.cycles,
+            interrupt_table: self.interrupt_table,
+            pending_interrupts: self.pending_interrupts,
+            memory: self.memory.contents()?,
+        })
+    }
+
+    /// Puts the machine back into the state of a snapshot. Signal
+    /// handlers and the debug flag are left as they are.
+    ///
+    /// # Errors
+    /// Fails if the memory does not support being restored or is not
+    /// the same size as the snapshot's. The machine is left unchanged.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+        self.memory.restore(&snapshot.memory)?;
+
+        self.registers = snapshot.registers;
+        self.machine_halted = snapshot.machine_halted;
+        self.cycles = snapshot.cycles;
+        self.interrupt_table = snapshot.interrupt_table;
+        self.pending_interrupts = snapshot.pending_interrupts;
+
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+
+        self.clear_decode_cache();
+
+        Ok(())
+    }
+
+    /// Starts recording the changes each step makes so that steps can
+    /// be undone with `step_back`. Up to `capacity` steps are kept.
+    ///
+    /// Writes by instructions are recorded when the old byte can be
+    /// read without side effects (see `Addressable::peek`). Writes made
+    /// by signal handlers or the host through `memory` are not.
+    pub fn enable_journal(&mut self, capacity: usize) {
+        self.journal = Some(Journal::new(capacity));
+    }
+
+    /// Stops recording steps and forgets the recorded ones.
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Amount of steps that can currently be undone.
+    #[must_use]
+    pub fn journal_len(&self) -> usize {
+        self.journal.as_ref().map_or(0, Journal::len)
+    }
+
+    /// Undoes up to `n` steps, returning how many were undone.
+    ///
+    /// # Errors
+    /// Fails if memory can not be written back.
+    pub fn step_back(&mut self, n: usize) -> Result<usize, Box<dyn std::error::Error>> {
+        for undone in 0..n {
+            let Some(entry) = self.journal.as_mut().and_then(Journal::pop) else {
+                return Ok(undone);
+            };
+
+            for (address, old) in entry.writes.iter().rev() {
+                self.memory.write(*address, *old)?;
+
+                if let Some(cache) = &mut self.decode_cache {
+                    cache.invalidate(*address);
+                }
+            }
+
+            self.registers = entry.registers;
+            self.machine_halted = entry.machine_halted;
+            self.cycles = entry.cycles;
+            self.pending_interrupts = entry.pending_interrupts;
+        }
+
+        Ok(n)
+    }
+
+    /// Undoes steps until just before the most recent recorded write
+    /// to `address`, returning how many steps were undone. Returns
+    /// `None` and leaves the machine as it is if no recorded step
+    /// wrote to the address.
+    ///
+    /// # Errors
+    /// Fails if memory can not be written back.
+    pub fn rewind_to_write(
+        &mut self,
+        address: u16,
+    ) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        let steps = self
+            .journal
+            .as_ref()
+            .and_then(|journal| journal.steps_since_write(address));
+
+        steps.map_or(Ok(None), |steps| self.step_back(steps).map(Some))
+    }
+
+    /// Keeps instructions decoded once they have run, so that running
+    /// them again skips reading and decoding them. Writes made by
+    /// instructions drop the instructions they overwrite.
+    ///
+    /// Writes made by signal handlers or the host through `memory` are
+    /// not seen, call `clear_decode_cache` after changing code that way.
+    /// Code is expected to be in memory that reads back what was written
+    /// to it, rather than in a device.
+    pub fn enable_decode_cache(&mut self) {
+        if self.decode_cache.is_none() {
+            self.decode_cache = Some(DecodeCache::new());
+        }
+    }
+
+    /// Stops caching decoded instructions and forgets the cached ones.
+    pub fn disable_decode_cache(&mut self) {
+        self.decode_cache = None;
+    }
+
+    /// Forgets every cached instruction, keeping the cache enabled.
+    pub fn clear_decode_cache(&mut self) {
+        if let Some(cache) = &mut self.decode_cache {
+            cache.clear();
+        }
+    }
+
+    /// Enables the decode cache and decodes the code in the `len` bytes
+    /// from `start` ahead of time, returning how many instructions were
+    /// decoded. Words that are not instructions, such as data, are skipped.
+    pub fn predecode(&mut self, start: u16, len: usize) -> usize {
+        let cache = self.decode_cache.get_or_insert_with(DecodeCache::new);
+        let end = usize::from(start).saturating_add(len).min(MAX_MEMORY_BYTES);
+
+        let mut decoded = 0;
+        let mut address = usize::from(start);
+
+        while let Ok(pc) = u16::try_from(address) {
+            if address >= end {
+                break;
+            }
+
+            match decode_at(self.memory.as_ref(), pc) {
+                Some(entry) => {
+                    cache.insert(pc, entry);
+                    address += usize::from(entry.size);
+                    decoded += 1;
+                }
+                None => address += 2,
+            }
+        }
+
+        decoded
+    }
+
+    /// Sends a `TraceRecord` to `sink` for every instruction executed
+    /// from now on, replacing any previous sink.
+    pub fn set_trace_sink(&mut self, sink: Box<dyn TraceSink>) {
+        self.trace_sink = Some(sink);
+    }
+
+    /// Stops tracing, giving back the sink so it can be flushed.
+    pub fn take_trace_sink(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.trace_sink.take()
+    }
+
+    /// Total cycles spent executing instructions so far.
+    #[must_use]
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The general purpose registers, as passed to a signal handler.
+    #[must_use]
+    pub const fn signal_args(&self) -> SignalArgs {
+        SignalArgs {
+            a: self.registers[Register::A as usize],
+            b: self.registers[Register::B as usize],
+            c: self.registers[Register::C as usize],
+            d: self.registers[Register::D as usize],
+        }
+    }
+
     /// Creates a handler for a signal. Signals are simply used to
-    /// communicate to the host from inside the machine.
-    pub fn define_handler(&mut self, id: u8, handler: SignalFunction) {
-        self.signal_handlers.insert(id, handler);
+    /// communicate to the host from inside the machine. Handlers can
+    /// capture state, and replace any previous handler for the signal.
+    ///
+    /// ```rust
+    /// use strawberryvm::prelude::*;
+    ///
+    /// let mut vm = Machine::new();
+    /// let mut printed = Vec::new();
+    ///
+    /// vm.define_handler(0xF1, move |machine| {
+    ///     printed.push(machine.signal_args().a);
+    ///     Ok(SignalAction::Continue)
+    /// });
+    /// ```
+    pub fn define_handler(
+        &mut self,
+        id: u8,
+        handler: impl FnMut(&mut Self) -> SignalResult + 'static,
+    ) {
+        self.signal_handlers.insert(id, Box::new(handler));
+    }
+
+    /// Removes the handler for a signal, returning it if there was one.
+    /// A handler can remove itself while it runs, it is then dropped
+    /// once it returns rather than being returned here.
+    ///
+    /// ```rust
+    /// use strawberryvm::prelude::*;
+    ///
+    /// let mut vm = Machine::new();
+    ///
+    /// // Runs on the first `Signal $F1` only.
+    /// vm.define_handler(0xF1, |machine| {
+    ///     machine.remove_handler(0xF1);
+    ///     Ok(SignalAction::Continue)
+    /// });
+    /// ```
+    pub fn remove_handler(&mut self, id: u8) -> Option<SignalHandler> {
+        if let Some((signal, removed)) = &mut self.running_handler {
+            if *signal == id {
+                *removed = true;
+            }
+        }
+
+        self.signal_handlers.remove(&id)
+    }
+
+    /// Runs the handler for `signal`. The handler is taken out of the
+    /// table while it runs so that it can be given `&mut self`.
+    fn call_handler(&mut self, signal: u8) -> Result<(), Fault> {
+        let mut handler = self
+            .signal_handlers
+            .remove(&signal)
+            .ok_or(Fault::UnknownSignal(signal))?;
+
+        let outer = self.running_handler.replace((signal, false));
+        let result = handler(self);
+        let removed = matches!(self.running_handler, Some((_, true)));
+        self.running_handler = outer;
+
+        // Keep a handler the callback defined for its own signal, and
+        // drop this one if it removed itself.
+        if !removed {
+            self.signal_handlers.entry(signal).or_insert(handler);
+        }
+
+        match result {
+            Ok(SignalAction::Continue) => Ok(()),
+            Ok(SignalAction::Halt) => {
+                self.machine_halted = true;
+                Ok(())
+            }
+            Err(e) => Err(Fault::SignalFailed(signal, e.to_string())),
+        }
+    }
+
+    /// Raises an external interrupt. It is serviced before the next
+    /// instruction once the `InterruptEnable` flag is set, and dropped
+    /// if the vector has no handler. Returns false if the vector is
+    /// out of range.
+    pub fn raise_interrupt(&mut self, vector: u8) -> bool {
+        if vector >= INTERRUPT_VECTORS {
+            return false;
+        }
+
+        self.pending_interrupts |= 1 << vector;
+        true
+    }
+
+    /// Enters the handler for `vector` by pushing the flags and program
+    /// counter and disabling interrupts. Returns false if the vector
+    /// has no handler installed.
+    fn enter_interrupt(&mut self, vector: u8) -> Result<bool, Fault> {
+        if vector >= INTERRUPT_VECTORS {
+            return Ok(false);
+        }
+
+        let entry = self.interrupt_table.wrapping_add(u16::from(vector) * 2);
+
+        let handler = self.read_memory_u16(entry)?;
+        if handler == 0 {
+            return Ok(false);
+        }
+
+        self.push(self.registers[Register::FL as usize])?;
+        self.push(self.registers[Register::PC as usize])?;
+
+        self.set_flag(Flag::InterruptEnable, false);
+        self.registers[Register::PC as usize] = handler;
+        self.cycles += INTERRUPT_CYCLES;
+
+        Ok(true)
+    }
+
+    /// Services the lowest pending external interrupt if interrupts
+    /// are enabled. Returns true if a handler was entered.
+    fn service_interrupts(&mut self) -> Result<bool, Fault> {
+        if self.pending_interrupts == 0 || !self.test_flag(Flag::InterruptEnable) {
+            return Ok(false);
+        }
+
+        let Some(vector) = (0..INTERRUPT_VECTORS).find(|v| self.pending_interrupts & (1 << v) != 0)
+        else {
+            return Ok(false);
+        };
+
+        self.pending_interrupts &= !(1 << vector);
+        self.enter_interrupt(vector)
+    }
+
+    /// Hands a fault to the guest's handler. Returns false if there
+    /// is no handler for it, or entering the handler failed too.
+    fn trap(&mut self, fault: &Fault) -> bool {
+        fault
+            .vector()
+            .is_some_and(|vector| matches!(self.enter_interrupt(vector), Ok(true)))
+    }
+
+    /// Reads a u16 from memory, converting memory errors into a `Fault`.
+    fn read_memory_u16(&mut self, addr: u16) -> Result<u16, Fault> {
+        let value = self
+            .memory
+            .read_u16(addr)
+            .map_err(|e| Fault::from_memory(addr, e.as_ref()))?;
+
+        if let Some(accesses) = &mut self.trace_accesses {
+            accesses.reads.push((addr, value));
+        }
+
+        Ok(value)
+    }
+
+    /// Writes a u16 to memory, converting memory errors into a `Fault`.
+    fn write_memory_u16(&mut self, addr: u16, value: u16) -> Result<(), Fault> {
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(addr);
+            cache.invalidate(addr.wrapping_add(1));
+        }
+
+        if let Some(journal) = &mut self.journal {
+            for address in [Some(addr), addr.checked_add(1)].into_iter().flatten() {
+                if let Some(old) = self.memory.peek(address) {
+                    journal.record_write(address, old);
+                }
+            }
+        }
+
+        if let Some(accesses) = &mut self.trace_accesses {
+            accesses.writes.push((addr, value));
+        }
+
+        self.memory
+            .write_u16(addr, value)
+            .map_err(|e| Fault::from_memory(addr, e.as_ref()))
+    }
+
+    /// Reads a byte from memory, converting memory errors into a `Fault`.
+    fn read_memory_u8(&mut self, addr: u16) -> Result<u8, Fault> {
+        let value = self
+            .memory
+            .read(addr)
+            .map_err(|e| Fault::from_memory(addr, e.as_ref()))?;
+
+        if let Some(accesses) = &mut self.trace_accesses {
+            accesses.byte_reads.push((addr, value));
+        }
+
+        Ok(value)
+    }
+
+    /// Writes a byte to memory, converting memory errors into a `Fault`.
+    fn write_memory_u8(&mut self, addr: u16, value: u8) -> Result<(), Fault> {
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(addr);
+        }
+
+        if let Some(journal) = &mut self.journal {
+            if let Some(old) = self.memory.peek(addr) {
+                journal.record_write(addr, old);
+            }
+        }
+
+        if let Some(accesses) = &mut self.trace_accesses {
+            accesses.byte_writes.push((addr, value));
+        }
+
+        self.memory
+            .write(addr, value)
+            .map_err(|e| Fault::from_memory(addr, e.as_ref()))
+    }
+
+    /// Loads the word at `address` into `dest`, shared by every
+    /// addressing mode of `Load`.
+    fn load(&mut self, dest: Register, address: u16) -> Result<(), Fault> {
+        self.registers[dest as usize] = self.read_memory_u16(address)?;
+        Ok(())
+    }
+
+    /// Stores `src` at `address`, shared by every addressing mode of `Store`.
+    fn store(&mut self, src: Register, address: u16) -> Result<(), Fault> {
+        self.write_memory_u16(address, self.registers[src as usize])
+    }
+
+    /// Loads the byte at `address` into `dest`, sign-extending it when
+    /// `signed` is set and zero-extending it otherwise.
+    fn load_byte(&mut self, dest: Register, address: u16, signed: bool) -> Result<(), Fault> {
+        let value = self.read_memory_u8(address)?;
+        let upper = if signed && value & 0x80 != 0 {
+            0xFF00
+        } else {
+            0
+        };
+        self.registers[dest as usize] = upper | u16::from(value);
+        Ok(())
+    }
+
+    /// Stores the low byte of `src` at `address`.
+    fn store_byte(&mut self, src: Register, address: u16) -> Result<(), Fault> {
+        let [value, _] = self.registers[src as usize].to_le_bytes();
+        self.write_memory_u8(address, value)
     }
 
     /// Used to push values to the stack. Will take in a u16, split it into two bytes
@@ -106,10 +664,12 @@ impl Machine {
     /// This can fail if you attempt to write out of the memory constraints. E.g.
     /// if the VM has 16KBs of RAM and you write to 16,385 (1 above 16kb), you will
     /// get an error
-    pub(crate) fn push(&mut self, v: u16) -> Result<(), Box<dyn std::error::Error>> {
+    pub(crate) fn push(&mut self, v: u16) -> Result<(), Fault> {
         let sp = self.registers[Register::SP as usize];
-        self.memory.write_u16(sp, v)?;
-        self.registers[Register::SP as usize] += 2;
+        let next = sp.checked_add(2).ok_or(Fault::OutOfBounds(sp))?;
+
+        self.write_memory_u16(sp, v)?;
+        self.registers[Register::SP as usize] = next;
         Ok(())
     }
 
@@ -119,17 +679,13 @@ impl Machine {
     /// # Errors
     /// This can fail if you attempt pop too many values and the stack poitner goes
     /// below zero, which is impossible for an unsigned integer.
-    pub(crate) fn pop(&mut self) -> Result<u16, Box<dyn std::error::Error>> {
+    pub(crate) fn pop(&mut self) -> Result<u16, Fault> {
         let Some(sp) = self.registers[Register::SP as usize].checked_sub(2) else {
-            return Err("Stack pointer went back too far".into());
+            return Err(Fault::StackUnderflow);
         };
 
-        self.registers[Register::SP as usize] -= 2;
-
-        self.memory
-            .read_u16(sp)
-            .map_err(|_| format!("Failed to read memory @ 0x{sp:X}"))
-            .map(Ok)?
+        self.registers[Register::SP as usize] = sp;
+        self.read_memory_u16(sp)
     }
 
     fn set_flag(&mut self, flag: Flag, set: bool) {
@@ -140,41 +696,280 @@ impl Machine {
         }
     }
 
-    const fn test_flag(&self, flag: Flag) -> bool {
+    /// Returns whether a flag is set in the flags register.
+    #[must_use]
+    pub const fn test_flag(&self, flag: Flag) -> bool {
         (self.registers[Register::FL as usize] & (flag as u16)) != 0
     }
 
+    /// Stores the result of an ALU operation in `dest`, setting the
+    /// `Negative` and `Compare` flags from it.
+    fn set_result(&mut self, dest: Register, result: u16) {
+        self.registers[dest as usize] = result;
+
+        self.set_flag(Flag::Negative, (result & 0x8000) != 0);
+        self.set_flag(Flag::Compare, result == 0);
+    }
+
+    /// Adds `b` to `dest`, shared by `Add` and `AddI`.
+    fn add(&mut self, dest: Register, b: u16) {
+        let a = self.registers[dest as usize];
+        let (result, carried) = a.overflowing_add(b);
+
+        // Signed overflow when both operands have a sign the result doesn't.
+        self.set_flag(Flag::Overflow, (a ^ result) & (b ^ result) & 0x8000 != 0);
+        self.set_flag(Flag::Carry, carried);
+        self.set_result(dest, result);
+    }
+
+    /// Shifts `dest` left by the low 4 bits of `amount`.
+    fn shift_left(&mut self, dest: Register, amount: u16) {
+        let shift_amount = amount & 0x0F;
+        let value = self.registers[dest as usize];
+
+        // Carry holds the last bit shifted out, and is left alone by a shift of 0.
+        if shift_amount > 0 {
+            self.set_flag(Flag::Carry, (value >> (16 - shift_amount)) & 1 != 0);
+        }
+
+        self.set_result(dest, value << shift_amount);
+    }
+
+    /// Shifts `dest` right by the low 4 bits of `amount`.
+    fn shift_right(&mut self, dest: Register, amount: u16) {
+        let shift_amount = amount & 0x0F;
+        let value = self.registers[dest as usize];
+
+        if shift_amount > 0 {
+            self.set_flag(Flag::Carry, (value >> (shift_amount - 1)) & 1 != 0);
+        }
+
+        self.set_result(dest, value >> shift_amount);
+    }
+
+    /// Multiplies `dest` by `b`, setting `Overflow` when the product
+    /// does not fit in 16 bits.
+    fn multiply(&mut self, dest: Register, b: u16) {
+        let (result, overflowed) = self.registers[dest as usize].overflowing_mul(b);
+
+        self.set_flag(Flag::Overflow, overflowed);
+        self.set_result(dest, result);
+    }
+
+    /// Divides `dest` by `divisor`, faulting when it is zero.
+    fn divide(&mut self, dest: Register, divisor: u16) -> Result<(), Fault> {
+        if divisor == 0 {
+            return Err(Fault::DivideByZero);
+        }
+
+        self.set_result(dest, self.registers[dest as usize] / divisor);
+        Ok(())
+    }
+
+    /// Computes `a - b`, setting the flags as both `Sub` and `Cmp` do.
+    /// Carry is set when `a < b` unsigned, and Negative and Overflow
+    /// differ when `a < b` signed.
+    fn subtract(&mut self, a: u16, b: u16) -> u16 {
+        let (result, borrowed) = a.overflowing_sub(b);
+
+        // Signed overflow when the operands' signs differ and the result's differs from `a`.
+        self.set_flag(Flag::Overflow, (a ^ b) & (a ^ result) & 0x8000 != 0);
+        self.set_flag(Flag::Carry, borrowed);
+        self.set_flag(Flag::Negative, (result & 0x8000) != 0);
+        self.set_flag(Flag::Compare, result == 0);
+
+        result
+    }
+
+    /// Whether the flags make a conditional jump other than `Je` and
+    /// `Jne` jump, following a `Cmp` of the form `Cmp a b`.
+    const fn condition_holds(&self, op: Instruction) -> bool {
+        let equal = self.test_flag(Flag::Compare);
+        let carry = self.test_flag(Flag::Carry);
+        let negative = self.test_flag(Flag::Negative);
+        let overflow = self.test_flag(Flag::Overflow);
+
+        // Signed less than, the sign of `a - b` is wrong when it overflowed.
+        let less = negative != overflow;
+
+        match op {
+            Instruction::Jl(_) => less,
+            Instruction::Jg(_) => !less && !equal,
+            Instruction::Jle(_) => less || equal,
+            Instruction::Jge(_) => !less,
+            Instruction::Jb(_) | Instruction::Jc(_) => carry,
+            Instruction::Ja(_) => !carry && !equal,
+            Instruction::Jo(_) => overflow,
+            Instruction::Jn(_) => negative,
+            _ => false,
+        }
+    }
+
+    /// Moves the program counter by a signed offset measured in
+    /// instructions (16-bit words) from the next instruction.
+    fn jump_relative(&mut self, offset: i8) {
+        let pc = self.registers[Register::PC as usize];
+        self.registers[Register::PC as usize] = pc.wrapping_add_signed(i16::from(offset) * 2);
+    }
+
     /// Used to step the machine forward, can be called by a
     /// virtual "clock" to simulate cpu cycles.
     ///
     /// # Errors
-    /// This can error if memory read fails (see `read_u16`).
-    /// It can also fail if memory popping fails.
-    /// It can also fail if a signal is non-existant.
-    /// It can also fail if the instruction is invalid.
-    pub fn step(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // sleep(Duration::from_millis(100));
+    /// Fails with a `VmError` describing the faulting instruction if
+    /// memory can not be accessed, the stack underflows, a signal has
+    /// no handler or the instruction is invalid. Faults are first handed
+    /// to the guest's interrupt handler if one is installed, in which case
+    /// execution continues at the handler instead.
+    pub fn step(&mut self) -> Result<(), VmError> {
         let pc = self.registers[Register::PC as usize];
-        self.registers[Register::PC as usize] += 2;
-        let instruction = self.memory.read_u16(pc)?;
 
-        // Snapshot state for panic reporting (panic hook must be `Send + Sync`, so it
-        // can't safely capture `&Machine`).
-        panic_report::set_last_status(format!(
-            "PC: 0x{pc:04X}\nINSTR: 0x{instruction:04X}\n{}",
-            self.status()
-        ));
+        if let Some(journal) = &mut self.journal {
+            journal.begin(Entry {
+                registers: self.registers,
+                machine_halted: self.machine_halted,
+                cycles: self.cycles,
+                pending_interrupts: self.pending_interrupts,
+                writes: Vec::new(),
+            });
+        }
+
+        match self.service_interrupts() {
+            Ok(true) => return Ok(()),
+            Ok(false) => (),
+            Err(fault) => {
+                return Err(VmError {
+                    pc,
+                    instruction: None,
+                    fault,
+                })
+            }
+        }
+
+        let cached = self.decode_cache.as_ref().and_then(|cache| cache.get(pc));
+
+        let (instruction, result) = match cached {
+            Some(decoded) => (Some(decoded.word), self.step_decoded(pc, decoded)),
+            None => match self.read_memory_u16(pc) {
+                Ok(instruction) => (Some(instruction), self.step_instruction(pc, instruction)),
+                Err(fault) => {
+                    self.registers[Register::PC as usize] = pc.wrapping_add(2);
+                    (None, Err(fault))
+                }
+            },
+        };
+
+        match result {
+            Err(fault) if !self.trap(&fault) => Err(VmError {
+                pc,
+                instruction,
+                fault,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Steps the machine until it halts or `budget` more cycles have
+    /// been spent. The last instruction may take the machine slightly
+    /// over budget, since instructions are never split.
+    ///
+    /// # Errors
+    /// Fails with the same `VmError` as `step`.
+    pub fn run_for(&mut self, budget: u64) -> Result<RunOutcome, VmError> {
+        let limit = self.cycles.saturating_add(budget);
+
+        while !self.machine_halted {
+            if self.cycles >= limit {
+                return Ok(RunOutcome::BudgetExhausted);
+            }
+
+            self.step()?;
+        }
+
+        Ok(RunOutcome::Halted)
+    }
+
+    fn step_instruction(&mut self, pc: u16, instruction: u16) -> Result<(), Fault> {
+        // Wide instructions carry an extension word straight after them.
+        let size = Instruction::encoded_size(instruction);
+        let extension = if size > 2 {
+            self.read_memory_u16(pc.wrapping_add(2))?
+        } else {
+            0
+        };
+
+        self.begin_instruction(pc, instruction, size);
 
-        let op = Instruction::try_from(instruction)?;
+        let op = Instruction::decode(instruction, extension)?;
 
+        if let Some(cache) = &mut self.decode_cache {
+            cache.insert(
+                pc,
+                Decoded {
+                    word: instruction,
+                    instruction: op,
+                    size,
+                },
+            );
+        }
+
+        self.run_instruction(pc, op)
+    }
+
+    /// Runs an instruction taken from the decode cache.
+    fn step_decoded(&mut self, pc: u16, decoded: Decoded) -> Result<(), Fault> {
+        self.begin_instruction(pc, decoded.word, decoded.size);
+        self.run_instruction(pc, decoded.instruction)
+    }
+
+    /// Moves the program counter past the instruction at `pc` and lets
+    /// devices tick, before the instruction is decoded.
+    fn begin_instruction(&mut self, pc: u16, instruction: u16, size: u16) {
+        self.registers[Register::PC as usize] = pc.wrapping_add(size);
+
+        self.memory.tick();
+
+        // Remember the state for panic reporting (panic hook must be `Send + Sync`, so
+        // it can't safely capture `&Machine`). It is only formatted after a panic.
+        panic_report::record(pc, instruction, &self.registers);
+    }
+
+    /// Charges the cycles of a decoded instruction and executes it,
+    /// tracing it when a sink is set.
+    fn run_instruction(&mut self, pc: u16, op: Instruction) -> Result<(), Fault> {
         if self.debug {
             println!("{pc:0>4} │ Got instruction `{op}`");
         }
 
+        self.cycles += op.cycles();
+
+        if self.trace_sink.is_none() {
+            return self.execute(op);
+        }
+
+        let before = self.registers;
+        self.trace_accesses = Some(Accesses::default());
+
+        let result = self.execute(op);
+
+        let accesses = self.trace_accesses.take().unwrap_or_default();
+        let record = TraceRecord::new(pc, op, &before, &self.registers, accesses, &result);
+
+        if let Some(sink) = &mut self.trace_sink {
+            sink.record(&record)
+                .map_err(|e| Fault::TraceFailed(e.to_string()))?;
+        }
+
+        result
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn execute(&mut self, op: Instruction) -> Result<(), Fault> {
         match op {
             Instruction::Nop => Ok(()),
 
             Instruction::Push(v) => self.push(u16::from(v)),
+            Instruction::PushWide(v) => self.push(v),
 
             Instruction::Pop(r) => {
                 let v = self.pop()?;
@@ -188,125 +983,106 @@ impl Machine {
             }
 
             Instruction::Add(dest, src) => {
-                let (result, overflowed) = self.registers[dest as usize]
-                    .overflowing_add(self.registers[src as usize]);
-
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Overflow, overflowed);
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
+                self.add(dest, self.registers[src as usize]);
+                Ok(())
+            }
 
+            Instruction::AddI(dest, imm) => {
+                self.add(dest, imm.into());
                 Ok(())
             }
 
             Instruction::Sub(dest, src) => {
-                let (result, overflowed) = self.registers[dest as usize]
-                    .overflowing_sub(self.registers[src as usize]);
+                let (a, b) = (self.registers[dest as usize], self.registers[src as usize]);
+                self.registers[dest as usize] = self.subtract(a, b);
 
-                self.registers[dest as usize] = result;
+                Ok(())
+            }
 
-                self.set_flag(Flag::Overflow, overflowed);
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
+            Instruction::SubI(dest, imm) => {
+                let a = self.registers[dest as usize];
+                self.registers[dest as usize] = self.subtract(a, imm.into());
 
                 Ok(())
             }
 
             Instruction::Shl(dest, src) => {
-                let shift_amount = self.registers[src as usize] & 0x0F;
-                let result = self.registers[dest as usize] << shift_amount;
-
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
+                self.shift_left(dest, self.registers[src as usize]);
+                Ok(())
+            }
 
+            Instruction::ShlI(dest, imm) => {
+                self.shift_left(dest, imm.into());
                 Ok(())
             }
 
             Instruction::Shr(dest, src) => {
-                let shift_amount = self.registers[src as usize] & 0x0F;
-                let result = self.registers[dest as usize] >> shift_amount;
-
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
+                self.shift_right(dest, self.registers[src as usize]);
+                Ok(())
+            }
 
+            Instruction::ShrI(dest, imm) => {
+                self.shift_right(dest, imm.into());
                 Ok(())
             }
 
             Instruction::And(dest, src) => {
                 let result = self.registers[dest as usize] & self.registers[src as usize];
+                self.set_result(dest, result);
+                Ok(())
+            }
 
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
-
+            Instruction::AndI(dest, imm) => {
+                let result = self.registers[dest as usize] & u16::from(imm);
+                self.set_result(dest, result);
                 Ok(())
             }
 
             Instruction::Or(dest, src) => {
                 let result = self.registers[dest as usize] | self.registers[src as usize];
+                self.set_result(dest, result);
+                Ok(())
+            }
 
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
-
+            Instruction::OrI(dest, imm) => {
+                let result = self.registers[dest as usize] | u16::from(imm);
+                self.set_result(dest, result);
                 Ok(())
             }
 
             Instruction::Xor(dest, src) => {
                 let result = self.registers[dest as usize] ^ self.registers[src as usize];
+                self.set_result(dest, result);
+                Ok(())
+            }
 
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
-
+            Instruction::XorI(dest, imm) => {
+                let result = self.registers[dest as usize] ^ u16::from(imm);
+                self.set_result(dest, result);
                 Ok(())
             }
 
             Instruction::Not(r) => {
                 let result = !self.registers[r as usize];
-
-                self.registers[r as usize] = result;
-
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
-
+                self.set_result(r, result);
                 Ok(())
             }
 
             Instruction::Mul(dest, src) => {
-                let (result, overflowed) = self.registers[dest as usize]
-                    .overflowing_mul(self.registers[src as usize]);
-
-                self.registers[dest as usize] = result;
-
-                self.set_flag(Flag::Overflow, overflowed);
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
-
+                self.multiply(dest, self.registers[src as usize]);
                 Ok(())
             }
 
-            Instruction::Div(dest, src) => {
-                let divisor = self.registers[src as usize];
-                if divisor == 0 {
-                    return Err("Division by zero".into());
-                }
-
-                let result = self.registers[dest as usize] / divisor;
-
-                self.registers[dest as usize] = result;
+            Instruction::MulI(dest, imm) => {
+                self.multiply(dest, imm.into());
+                Ok(())
+            }
 
-                self.set_flag(Flag::Negative, (result & 0x8000) != 0);
-                self.set_flag(Flag::Compare, result == 0);
+            Instruction::Div(dest, src) => self.divide(dest, self.registers[src as usize]),
+            Instruction::DivI(dest, imm) => self.divide(dest, imm.into()),
 
+            Instruction::LoadImm(dest, value) => {
+                self.registers[dest as usize] = value;
                 Ok(())
             }
 
@@ -317,77 +1093,257 @@ impl Machine {
             }
 
             Instruction::Cmp(a, b) => {
-                let val_a = self.registers[a as usize];
-                let val_b = self.registers[b as usize];
-
-                self.set_flag(Flag::Compare, val_a == val_b);
-                self.set_flag(Flag::Negative, val_a < val_b);
-                // Overflow flag is not typically set for comparisons
+                self.subtract(self.registers[a as usize], self.registers[b as usize]);
+                Ok(())
+            }
 
+            Instruction::CmpI(a, imm) => {
+                self.subtract(self.registers[a as usize], imm.into());
                 Ok(())
             }
 
             Instruction::Jmp(offset) => {
-                let pc = self.registers[Register::PC as usize];
-                let new_pc = if offset.is_negative() {
-                    pc.wrapping_sub(offset.wrapping_abs() as u16 * 2)
-                } else {
-                    pc.wrapping_add(offset as u16 * 2)
-                };
-
-                self.registers[Register::PC as usize] = new_pc;
+                self.jump_relative(offset);
                 Ok(())
             }
 
             Instruction::Je(offset) => {
                 if self.test_flag(Flag::Compare) {
-                    let pc = self.registers[Register::PC as usize];
-                    let new_pc = if offset.is_negative() {
-                        pc.wrapping_sub(offset.wrapping_abs() as u16 * 2)
-                    } else {
-                        pc.wrapping_add(offset as u16 * 2)
-                    };
-
-                    self.registers[Register::PC as usize] = new_pc;
+                    self.jump_relative(offset);
                 }
                 Ok(())
             }
 
             Instruction::Jne(offset) => {
                 if !self.test_flag(Flag::Compare) {
-                    let pc = self.registers[Register::PC as usize];
-                    let new_pc = if offset.is_negative() {
-                        pc.wrapping_sub(offset.wrapping_abs() as u16 * 2)
-                    } else {
-                        pc.wrapping_add(offset as u16 * 2)
-                    };
-
-                    self.registers[Register::PC as usize] = new_pc;
+                    self.jump_relative(offset);
                 }
                 Ok(())
             }
 
-            Instruction::Load(dest, src) => {
-                let address = self.registers[src as usize];
-                let value = self.memory.read_u16(address)?;
-                self.registers[dest as usize] = value;
+            Instruction::Jl(offset)
+            | Instruction::Jg(offset)
+            | Instruction::Jle(offset)
+            | Instruction::Jge(offset)
+            | Instruction::Jb(offset)
+            | Instruction::Ja(offset)
+            | Instruction::Jc(offset)
+            | Instruction::Jo(offset)
+            | Instruction::Jn(offset) => {
+                if self.condition_holds(op) {
+                    self.jump_relative(offset);
+                }
                 Ok(())
             }
 
-            Instruction::Store(src, dest) => {
-                let address = self.registers[dest as usize];
-                let value = self.registers[src as usize];
-                self.memory.write_u16(address, value)?;
+            Instruction::Load(dest, src) => self.load(dest, self.registers[src as usize]),
+            Instruction::LoadOff(dest, at) => self.load(dest, at.address(&self.registers)),
+            Instruction::LoadIdx(dest, at) => self.load(dest, at.address(&self.registers)),
+
+            Instruction::Store(src, dest) => self.store(src, self.registers[dest as usize]),
+            Instruction::StoreOff(src, at) => self.store(src, at.address(&self.registers)),
+            Instruction::StoreIdx(src, at) => self.store(src, at.address(&self.registers)),
+
+            Instruction::LoadB(dest, src) => {
+                self.load_byte(dest, self.registers[src as usize], false)
+            }
+            Instruction::LoadBOff(dest, at) => {
+                self.load_byte(dest, at.address(&self.registers), false)
+            }
+            Instruction::LoadBIdx(dest, at) => {
+                self.load_byte(dest, at.address(&self.registers), false)
+            }
+
+            Instruction::LoadBS(dest, src) => {
+                self.load_byte(dest, self.registers[src as usize], true)
+            }
+            Instruction::LoadBSOff(dest, at) => {
+                self.load_byte(dest, at.address(&self.registers), true)
+            }
+            Instruction::LoadBSIdx(dest, at) => {
+                self.load_byte(dest, at.address(&self.registers), true)
+            }
+
+            Instruction::StoreB(src, dest) => self.store_byte(src, self.registers[dest as usize]),
+            Instruction::StoreBOff(src, at) => self.store_byte(src, at.address(&self.registers)),
+            Instruction::StoreBIdx(src, at) => self.store_byte(src, at.address(&self.registers)),
+
+            Instruction::Call(offset) => {
+                self.push(self.registers[Register::PC as usize])?;
+                self.jump_relative(offset);
+                Ok(())
+            }
+
+            Instruction::CallReg(r) => {
+                let target = self.registers[r as usize];
+                self.push(self.registers[Register::PC as usize])?;
+                self.registers[Register::PC as usize] = target;
+                Ok(())
+            }
+
+            Instruction::Ret => {
+                let address = self.pop()?;
+                self.registers[Register::PC as usize] = address;
+                Ok(())
+            }
+
+            Instruction::Int(vector) => {
+                if self.enter_interrupt(vector)? {
+                    Ok(())
+                } else {
+                    Err(Fault::UnhandledInterrupt(vector))
+                }
+            }
+
+            Instruction::Iret => {
+                let address = self.pop()?;
+                let flags = self.pop()?;
+
+                self.registers[Register::PC as usize] = address;
+                self.registers[Register::FL as usize] = flags;
                 Ok(())
             }
 
-            Instruction::Signal(signal) => {
-                self.signal_handlers
-                    .get(&signal)
-                    .ok_or(format!("Unknown signal 0x{signal:X}"))?(self);
+            Instruction::Ei => {
+                self.set_flag(Flag::InterruptEnable, true);
+                Ok(())
+            }
 
+            Instruction::Di => {
+                self.set_flag(Flag::InterruptEnable, false);
                 Ok(())
             }
+
+            Instruction::Signal(signal) => self.call_handler(signal),
+        }
+    }
+}
+
+/// Used to configure a `Machine` before creating it.
+///
+/// ```rust
+/// use strawberryvm::prelude::*;
+///
+/// let vm = Machine::builder()
+///     .memory_size(MAX_MEMORY_BYTES)
+///     .stack_pointer(0x8000)
+///     .build()
+///     .expect("64KiB fits in the address space");
+///
+/// assert_eq!(vm.get_register(Register::SP), 0x8000);
+/// ```
+pub struct MachineBuilder {
+    memory: Option<Box<dyn memory::Addressable>>,
+    memory_size: usize,
+
+    stack_pointer: u16,
+    program_counter: u16,
+    base_pointer: u16,
+
+    interrupt_table: Option<u16>,
+}
+
+impl Default for MachineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineBuilder {
+    /// Creates a builder with the same defaults as `Machine::new`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            memory: None,
+            memory_size: MEMORY_KILO_BYTES * 1024,
+
+            stack_pointer: 0,
+            program_counter: 0,
+            base_pointer: 0,
+
+            interrupt_table: None,
         }
     }
+
+    /// Sets the size in bytes of the linear memory, at most
+    /// `MAX_MEMORY_BYTES`. Ignored if a custom memory is given.
+    #[must_use]
+    pub const fn memory_size(mut self, bytes: usize) -> Self {
+        self.memory_size = bytes;
+        self
+    }
+
+    /// Uses a custom memory implementation instead of linear memory.
+    #[must_use]
+    pub fn memory(mut self, memory: Box<dyn memory::Addressable>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Sets the initial value of the stack pointer.
+    #[must_use]
+    pub const fn stack_pointer(mut self, sp: u16) -> Self {
+        self.stack_pointer = sp;
+        self
+    }
+
+    /// Sets the initial value of the program counter.
+    #[must_use]
+    pub const fn program_counter(mut self, pc: u16) -> Self {
+        self.program_counter = pc;
+        self
+    }
+
+    /// Sets the initial value of the base pointer.
+    #[must_use]
+    pub const fn base_pointer(mut self, bp: u16) -> Self {
+        self.base_pointer = bp;
+        self
+    }
+
+    /// Sets the address of the interrupt vector table. Defaults to the
+    /// last 32 bytes of linear memory, or `DEFAULT_INTERRUPT_TABLE` when
+    /// a custom memory is given.
+    #[must_use]
+    pub const fn interrupt_table(mut self, addr: u16) -> Self {
+        self.interrupt_table = Some(addr);
+        self
+    }
+
+    /// Creates the machine.
+    ///
+    /// # Errors
+    /// Fails if the memory size is zero or larger than
+    /// the 16-bit address space.
+    pub fn build(self) -> Result<Machine, Box<dyn std::error::Error>> {
+        let default_table = if self.memory.is_some() {
+            DEFAULT_INTERRUPT_TABLE
+        } else {
+            interrupt_table_for(self.memory_size)
+        };
+
+        let memory = if let Some(memory) = self.memory {
+            memory
+        } else {
+            if self.memory_size == 0 || self.memory_size > MAX_MEMORY_BYTES {
+                return Err(format!(
+                    "Memory size must be between 1 and {MAX_MEMORY_BYTES} bytes, got {}",
+                    self.memory_size
+                )
+                .into());
+            }
+
+            Box::new(memory::Linear::new(self.memory_size))
+        };
+
+        let mut machine = Machine::with_memory(memory);
+
+        machine.registers[Register::SP as usize] = self.stack_pointer;
+        machine.registers[Register::PC as usize] = self.program_counter;
+        machine.registers[Register::BP as usize] = self.base_pointer;
+
+        machine.interrupt_table = self.interrupt_table.unwrap_or(default_table);
+
+        Ok(machine)
+    }
 }